@@ -0,0 +1,175 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tracing::{debug, info};
+
+use crate::config::ChordConfig;
+
+#[derive(Debug, Default)]
+struct State {
+    chords: Vec<ChordConfig>,
+    /// Names of the corners that were entered and when, oldest first.
+    history: Vec<(String, Instant)>,
+}
+
+/// Detects chords across all corners, fed by the enter events of every corner.
+#[derive(Debug, Default)]
+pub struct Chords {
+    state: Mutex<State>,
+}
+
+impl Chords {
+    pub fn new(chords: Vec<ChordConfig>) -> Chords {
+        Chords {
+            state: Mutex::new(State {
+                chords,
+                history: vec![],
+            }),
+        }
+    }
+
+    /// Replace the chords, forgetting the corners that were entered before.
+    pub fn set_chords(&self, chords: Vec<ChordConfig>) {
+        let mut state = self.state.lock().expect("cannot get chords");
+        state.chords = chords;
+        state.history.clear();
+    }
+
+    /// Record that the corner was entered and return the chord it completed, if any.
+    pub fn enter(&self, corner: &str) -> Option<ChordConfig> {
+        self.enter_at(corner, Instant::now())
+    }
+
+    fn enter_at(&self, corner: &str, now: Instant) -> Option<ChordConfig> {
+        let mut state = self.state.lock().expect("cannot get chords");
+        let longest_window = state
+            .chords
+            .iter()
+            .map(|chord| chord.window_ms)
+            .max()
+            .map(|value| Duration::from_millis(value.into()))?;
+
+        state
+            .history
+            .retain(|(_, at)| now.duration_since(*at) <= longest_window);
+        state.history.push((corner.to_owned(), now));
+        debug!("Corners entered for chords: {:?}", state.history);
+
+        let history = &state.history;
+        let chord = state
+            .chords
+            .iter()
+            .find(|chord| {
+                let Some(start) = history.len().checked_sub(chord.sequence.len()) else {
+                    return false;
+                };
+                let entered = &history[start..];
+                entered
+                    .iter()
+                    .zip(&chord.sequence)
+                    .all(|((name, _), expected)| name == expected)
+                    && entered.first().is_some_and(|(_, at)| {
+                        now.duration_since(*at) <= Duration::from_millis(chord.window_ms.into())
+                    })
+            })
+            .cloned();
+        if let Some(chord) = &chord {
+            info!("Completed chord {}", chord.name);
+            state.history.clear();
+        }
+        chord
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CommandConfig;
+
+    fn chord(name: &str, sequence: &[&str], window_ms: u32) -> ChordConfig {
+        ChordConfig {
+            name: name.to_owned(),
+            sequence: sequence.iter().map(|corner| (*corner).to_owned()).collect(),
+            window_ms,
+            command: CommandConfig::Shell("true".to_owned()),
+        }
+    }
+
+    /// Enter the corners at the given milliseconds and return the names of the completed chords.
+    fn enter(chords: &Chords, entered: &[(&str, u64)]) -> Vec<Option<String>> {
+        let start = Instant::now();
+        entered
+            .iter()
+            .map(|(corner, at)| {
+                chords
+                    .enter_at(corner, start + Duration::from_millis(*at))
+                    .map(|chord| chord.name)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn completes_sequence() {
+        let chords = Chords::new(vec![chord("a", &["left", "right"], 1000)]);
+        assert_eq!(
+            enter(&chords, &[("left", 0), ("right", 500)]),
+            [None, Some("a".to_owned())]
+        );
+    }
+
+    #[test]
+    fn ignores_out_of_order_sequence() {
+        let chords = Chords::new(vec![chord("a", &["left", "right"], 1000)]);
+        assert_eq!(
+            enter(&chords, &[("right", 0), ("left", 100), ("top", 200)]),
+            [None, None, None]
+        );
+        assert_eq!(
+            enter(&chords, &[("left", 0), ("top", 100), ("right", 200)]),
+            [None, None, None]
+        );
+    }
+
+    #[test]
+    fn sequence_has_to_fit_in_window() {
+        let chords = Chords::new(vec![chord("a", &["left", "right"], 1000)]);
+        assert_eq!(
+            enter(&chords, &[("left", 0), ("right", 1001)]),
+            [None, None]
+        );
+        assert_eq!(
+            enter(&chords, &[("left", 0), ("left", 600), ("right", 1200)]),
+            [None, None, Some("a".to_owned())]
+        );
+    }
+
+    #[test]
+    fn windows_are_per_chord() {
+        let chords = Chords::new(vec![
+            chord("short", &["left", "right"], 100),
+            chord("long", &["top", "bottom"], 2000),
+        ]);
+        assert_eq!(enter(&chords, &[("left", 0), ("right", 500)]), [None, None]);
+        assert_eq!(
+            enter(&chords, &[("top", 0), ("bottom", 1500)]),
+            [None, Some("long".to_owned())]
+        );
+    }
+
+    #[test]
+    fn completing_forgets_history() {
+        let chords = Chords::new(vec![chord("a", &["left", "left"], 1000)]);
+        assert_eq!(
+            enter(&chords, &[("left", 0), ("left", 100), ("left", 200)]),
+            [None, Some("a".to_owned()), None]
+        );
+    }
+
+    #[test]
+    fn without_chords() {
+        let chords = Chords::default();
+        assert_eq!(enter(&chords, &[("left", 0), ("left", 100)]), [None, None]);
+    }
+}
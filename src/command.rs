@@ -0,0 +1,259 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    env,
+    os::unix::process::CommandExt,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use nix::{
+    sys::signal::{killpg, Signal},
+    unistd::Pid,
+};
+use serde::Deserialize;
+use tracing::{debug, info, warn};
+
+/// What to do when a command of a corner should run while a previous one is still running.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OnBusy {
+    /// Do not run the new command.
+    Ignore,
+    /// Run the new command once the running ones exited.
+    Queue,
+    /// Kill the running commands and run the new command.
+    Restart,
+    /// Run the new command next to the running ones.
+    #[default]
+    Parallel,
+}
+
+struct Running {
+    child: Child,
+    started_at: Instant,
+    /// Whether the corner ignores events for a moment after the command exited.
+    locks: bool,
+}
+
+/// Spawns the commands of a corner without waiting for them and reaps them afterwards.
+pub struct Runner {
+    on_busy: OnBusy,
+    timeout: Option<Duration>,
+    running: Vec<Running>,
+    queue: VecDeque<(Command, bool)>,
+}
+
+impl Runner {
+    pub fn new(on_busy: OnBusy, timeout: Option<Duration>) -> Runner {
+        Runner {
+            on_busy,
+            timeout,
+            running: vec![],
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// Run the command, `locks` tells whether its exit should make the corner ignore events for
+    /// a moment.
+    pub fn run(&mut self, command: Command, locks: bool) {
+        if !self.running.is_empty() {
+            match self.on_busy {
+                OnBusy::Ignore => {
+                    info!("Ignoring command as a previous one is still running");
+                    return;
+                }
+                OnBusy::Queue => {
+                    info!("Queueing command as a previous one is still running");
+                    self.queue.push_back((command, locks));
+                    return;
+                }
+                OnBusy::Restart => {
+                    info!("Killing the running commands");
+                    self.running.iter_mut().for_each(kill);
+                    self.reap();
+                }
+                OnBusy::Parallel => (),
+            }
+        }
+        self.spawn(command, locks)
+    }
+
+    /// Spawn the command, failing to do so is logged so the corner keeps working.
+    fn spawn(&mut self, mut command: Command, locks: bool) {
+        // Give the command its own process group so it can be killed with its children.
+        match command.stdin(Stdio::null()).process_group(0).spawn() {
+            Ok(child) => {
+                debug!("Spawned command with pid {}", child.id());
+                self.running.push(Running {
+                    child,
+                    started_at: Instant::now(),
+                    locks,
+                });
+            }
+            Err(error) => warn!(
+                "Could not run command {:?}: {:?}",
+                command.get_program(),
+                error
+            ),
+        }
+    }
+
+    /// Kill the commands that exceeded their timeout, reap the ones that exited and start the
+    /// next queued command. Returns whether any command that locks the corner exited.
+    pub fn poll(&mut self) -> bool {
+        if let Some(timeout) = self.timeout {
+            self.running
+                .iter_mut()
+                .filter(|running| running.started_at.elapsed() >= timeout)
+                .for_each(|running| {
+                    info!("Command {} timed out", running.child.id());
+                    kill(running);
+                });
+        }
+        let locked = self.reap();
+        if self.running.is_empty() {
+            if let Some((command, locks)) = self.queue.pop_front() {
+                self.spawn(command, locks);
+            }
+        }
+        locked
+    }
+
+    fn reap(&mut self) -> bool {
+        let mut locked = false;
+        self.running
+            .retain_mut(|running| match running.child.try_wait() {
+                Ok(Some(status)) => {
+                    info!("Command {} exited: {}", running.child.id(), status);
+                    locked |= running.locks;
+                    false
+                }
+                Ok(None) => true,
+                Err(error) => {
+                    warn!(
+                        "Could not wait for command {}: {:?}",
+                        running.child.id(),
+                        error
+                    );
+                    locked |= running.locks;
+                    false
+                }
+            });
+        locked
+    }
+}
+
+fn kill(running: &mut Running) {
+    let pid = Pid::from_raw(running.child.id() as i32);
+    if let Err(error) = killpg(pid, Signal::SIGKILL) {
+        debug!("Could not kill process group {}: {:?}", pid, error);
+    }
+    // Wait for the command itself so it does not become a zombie.
+    running.child.wait().ok();
+}
+
+/// Expand a leading `~` to `$HOME` and `$VAR` or `${VAR}` to the value of the variable, looking
+/// in `variables` first and then in the environment. Unset variables are left as written, so
+/// they still reach a shell that is run with `-c`.
+pub fn expand(value: &str, variables: &HashMap<String, String>) -> String {
+    let lookup = |name: &str| variables.get(name).cloned().or_else(|| env::var(name).ok());
+
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    if rest == "~" || rest.starts_with("~/") {
+        if let Some(home) = lookup("HOME") {
+            result.push_str(&home);
+            rest = &rest[1..];
+        }
+    }
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let candidate = &rest[start + 1..];
+        let (name, length) = match candidate.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = candidate
+                    .find(|character: char| {
+                        !(character.is_ascii_alphanumeric() || character == '_')
+                    })
+                    .unwrap_or(candidate.len());
+                (&candidate[..end], end)
+            }
+        };
+        match Some(name).filter(|name| !name.is_empty()).and_then(lookup) {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[start..start + 1 + length]),
+        }
+        rest = &candidate[length..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Name of the environment variable commands receive the variable of the key as.
+pub fn variable_name(key: &str) -> String {
+    format!("WAYCORNER_{}", key.to_uppercase())
+}
+
+/// Reference to the environment variable of the key, which the shell expands as a single word.
+/// Its value never becomes part of the script, so text that other programs control, like the
+/// selection, cannot run as shell code.
+pub fn shell_variable(key: &str) -> String {
+    format!("\"${}\"", variable_name(key))
+}
+
+/// The shell to run shell string commands with.
+pub fn shell() -> String {
+    env::var("SHELL")
+        .ok()
+        .filter(|value| !value.is_empty())
+        .unwrap_or_else(|| "/bin/sh".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> HashMap<String, String> {
+        HashMap::from([
+            ("HOME".to_owned(), "/home/user".to_owned()),
+            ("NAME".to_owned(), "value".to_owned()),
+        ])
+    }
+
+    #[test]
+    fn expand_variables() {
+        assert_eq!(expand("$NAME", &variables()), "value");
+        assert_eq!(expand("a-${NAME}-b", &variables()), "a-value-b");
+        assert_eq!(expand("$NAME/$NAME", &variables()), "value/value");
+    }
+
+    #[test]
+    fn expand_home() {
+        assert_eq!(expand("~", &variables()), "/home/user");
+        assert_eq!(expand("~/Downloads", &variables()), "/home/user/Downloads");
+        assert_eq!(expand("~user/Downloads", &variables()), "~user/Downloads");
+        assert_eq!(expand("a~/b", &variables()), "a~/b");
+    }
+
+    #[test]
+    fn expand_keeps_unknown_variables() {
+        let unknown = "$WAYCORNER_TEST_UNSET ${WAYCORNER_TEST_UNSET}";
+        assert_eq!(expand(unknown, &variables()), unknown);
+        assert_eq!(
+            expand("echo \"$1\" $NAME", &variables()),
+            "echo \"$1\" value"
+        );
+    }
+
+    #[test]
+    fn expand_keeps_lone_dollars() {
+        assert_eq!(expand("$", &variables()), "$");
+        assert_eq!(expand("5$ and $$", &variables()), "5$ and $$");
+        assert_eq!(expand("${}", &variables()), "${}");
+        assert_eq!(expand("${NAME", &variables()), "${NAME");
+    }
+}
@@ -1,4 +1,10 @@
-use std::{collections::HashMap, env, fs::File, io::Read, path::PathBuf};
+use std::{
+    collections::HashMap,
+    env, fmt,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{bail, Context, Result};
 use regex::Regex;
@@ -8,9 +14,22 @@ use serde::{
 };
 use tracing::{debug, info};
 
+use crate::command::{self, OnBusy};
+
 pub const COLOR_TRANSPARENT: u32 = 0x00_00_00_00;
 pub const COLOR_RED: u32 = 0xFF_FF_00_00;
 
+/// Names of the buttons that can be used in `button_commands`, with their Linux input event code.
+pub const BUTTONS: [(&str, u32); 7] = [
+    ("left", 0x110),
+    ("right", 0x111),
+    ("middle", 0x112),
+    ("side", 0x113),
+    ("extra", 0x114),
+    ("forward", 0x115),
+    ("back", 0x116),
+];
+
 fn default_locations() -> Vec<Location> {
     vec![Location::BottomRight, Location::BottomLeft]
 }
@@ -27,12 +46,28 @@ fn default_timeout_ms() -> u16 {
     250
 }
 
+fn default_slider_interval_ms() -> u16 {
+    100
+}
+
+fn default_swipe_distance() -> u32 {
+    50
+}
+
+fn default_hits() -> u8 {
+    1
+}
+
+fn default_hit_window_ms() -> u16 {
+    600
+}
+
 fn default_color() -> u32 {
     COLOR_RED
 }
 
-fn default_command() -> Vec<String> {
-    Vec::new()
+fn default_command() -> CommandConfig {
+    CommandConfig::Argv(Vec::new())
 }
 
 fn from_hex<'de, D>(deserializer: D) -> Result<u32, D::Error>
@@ -60,31 +95,403 @@ where
         .map_err(de::Error::custom)
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct CornerConfig {
+    /// Name of the TOML table the corner was defined in.
+    #[serde(skip)]
+    pub name: String,
     pub output: Option<OutputConfig>,
     #[serde(default = "default_command", alias = "command")]
-    pub enter_command: Vec<String>,
+    pub enter_command: CommandConfig,
     #[serde(default = "default_command")]
-    pub exit_command: Vec<String>,
+    pub exit_command: CommandConfig,
+    /// Built-in action to run when the corner triggers, next to the enter command.
+    pub action: Option<Action>,
+    #[serde(default)]
+    pub restore: Restore,
+    /// Directions the pointer has to leave the corner in to run the exit command, all
+    /// directions when empty.
+    #[serde(default)]
+    pub exit_directions: Vec<ExitDirection>,
     #[serde(default = "default_locations")]
     pub locations: Vec<Location>,
     #[serde(default = "default_size")]
     pub size: u8,
+    /// Size the corner grows to while the pointer is in it.
+    pub leave_size: Option<u8>,
+    /// When only a sliver at the border of the output takes the input of the corner.
+    #[serde(default)]
+    pub click_through: ClickThrough,
     #[serde(default = "default_margin")]
     pub margin: i8,
     #[serde(default = "default_timeout_ms")]
     pub timeout_ms: u16,
+    /// Minimum speed in pixels per second at which the pointer has to enter the corner.
+    pub min_velocity: Option<u32>,
+    /// Directions the pointer may enter the corner from, all directions when empty.
+    #[serde(default)]
+    pub approach: Vec<Approach>,
+    /// What to do when the corner triggers while a button is held, drags only enter the corner
+    /// when this is set.
+    pub while_dragging: Option<WhileDragging>,
+    /// Command to run instead of the enter command when `while_dragging` is `command`.
+    #[serde(default = "default_command")]
+    pub drag_command: CommandConfig,
+    /// How many times the corner has to be entered within `hit_window_ms` to run the enter
+    /// command.
+    #[serde(default = "default_hits")]
+    pub hits: u8,
+    #[serde(default = "default_hit_window_ms")]
+    pub hit_window_ms: u16,
+    /// Command to run when the corner was entered fewer than `hits` times within the window.
+    #[serde(default = "default_command")]
+    pub single_hit_command: CommandConfig,
     #[serde(default = "default_color", deserialize_with = "from_hex")]
     pub color: u32,
+    #[serde(default)]
+    pub on_busy: OnBusy,
+    pub command_timeout_ms: Option<u32>,
+    /// Extra environment variables for the commands.
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    pub working_directory: Option<String>,
+    /// Commands to run when a button is pressed in the corner, keyed by button name.
+    #[serde(default)]
+    pub button_commands: HashMap<String, CommandConfig>,
+    #[serde(default = "default_command")]
+    pub scroll_up_command: CommandConfig,
+    #[serde(default = "default_command")]
+    pub scroll_down_command: CommandConfig,
+    #[serde(default = "default_command")]
+    pub scroll_left_command: CommandConfig,
+    #[serde(default = "default_command")]
+    pub scroll_right_command: CommandConfig,
+    /// Command to run with the position along an edge as a value from 0 to 100.
+    #[serde(default = "default_command")]
+    pub slider_command: CommandConfig,
+    /// Minimum time between two runs of the slider command.
+    #[serde(default = "default_slider_interval_ms")]
+    pub slider_interval_ms: u16,
+    /// Whether a tablet tool like a pen hovering over the corner enters it like the pointer.
+    #[serde(default)]
+    pub tablet: bool,
+    /// Command to run when a touch starts on the corner and moves inward.
+    #[serde(default = "default_command")]
+    pub swipe_command: CommandConfig,
+    /// Distance in pixels a touch has to move inward to run the swipe command.
+    #[serde(default = "default_swipe_distance")]
+    pub swipe_distance: u32,
+    /// Command to run with the files or text dropped onto the corner as arguments.
+    #[serde(default = "default_command")]
+    pub drop_command: CommandConfig,
+    /// MIME types one of which a drag has to offer to be dropped onto the corner, like
+    /// `text/uri-list` or `image/*`. Any text or files when empty.
+    #[serde(default)]
+    pub drop_mime_types: Vec<String>,
+    /// Whether to show the corner while a drag that can be dropped onto it hovers over it.
+    #[serde(default)]
+    pub drop_highlight: bool,
+    /// Run the enter command again every `repeat_ms` while the pointer stays in the corner.
+    pub repeat_ms: Option<u32>,
+    /// Maximum number of times the enter command is repeated.
+    pub repeat_limit: Option<u32>,
+    /// Commands that run once the pointer stayed in the corner for a while.
+    #[serde(default, rename = "stage")]
+    pub stages: Vec<StageConfig>,
+    /// Parts of an edge with their own commands.
+    #[serde(default, rename = "zone")]
+    pub zones: Vec<ZoneConfig>,
+}
+
+impl CornerConfig {
+    /// Whether the corner has any command to run.
+    fn has_commands(&self) -> bool {
+        !(self.action.is_none()
+            && self.enter_command.is_empty()
+            && self.exit_command.is_empty()
+            && self.single_hit_command.is_empty()
+            && self.drag_command.is_empty()
+            && self.stages.is_empty()
+            && self.button_commands.is_empty()
+            && self.slider_command.is_empty()
+            && self.swipe_command.is_empty()
+            && self.drop_command.is_empty()
+            && self
+                .zones
+                .iter()
+                .all(|zone| zone.enter_command.is_empty() && zone.exit_command.is_empty())
+            && ["up", "down", "left", "right"]
+                .iter()
+                .all(|direction| self.scroll_command(direction).is_empty()))
+    }
+
+    pub fn while_dragging(&self) -> WhileDragging {
+        self.while_dragging.unwrap_or_default()
+    }
+
+    /// Whether drag-and-drop enters the corner, only corners that opt in to drags do so existing
+    /// corners do not trigger while something is dragged over them.
+    pub fn accepts_drags(&self) -> bool {
+        self.while_dragging.is_some() || !self.drop_command.is_empty()
+    }
+
+    pub fn scroll_command(&self, direction: &str) -> &CommandConfig {
+        match direction {
+            "up" => &self.scroll_up_command,
+            "down" => &self.scroll_down_command,
+            "left" => &self.scroll_left_command,
+            _ => &self.scroll_right_command,
+        }
+    }
+
+    /// Whether the corner needs to know where the pointer is on its surfaces.
+    pub fn tracks_motion(&self) -> bool {
+        !self.slider_command.is_empty()
+            || !self.zones.is_empty()
+            || !self.exit_command.is_empty()
+            || self.filters_approach()
+    }
+
+    /// Whether entering the corner depends on how the pointer approached it.
+    pub fn filters_approach(&self) -> bool {
+        self.min_velocity.is_some() || !self.approach.is_empty()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct StageConfig {
+    /// Milliseconds after entering the corner.
+    pub after_ms: u32,
+    pub command: CommandConfig,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+/// When clicks on a corner reach the window below it, except for a sliver of one pixel at the
+/// border of the output.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClickThrough {
+    #[default]
+    Never,
+    Always,
+    /// After the corner was triggered, until the pointer left the sliver.
+    AfterTrigger,
+}
+
+/// What to do when a corner triggers while a button is held, like when dragging a window.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum WhileDragging {
+    /// Do not run any command.
+    Ignore,
+    /// Run the commands as if no button is held.
+    #[default]
+    Trigger,
+    /// Run the `drag_command` instead of the enter command.
+    Command,
+}
+
+/// A built-in action that runs next to the enter command.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Minimise all windows on the output of the corner.
+    ShowDesktop,
+}
+
+/// When to undo the built-in action of a corner.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Restore {
+    /// Never undo the action.
+    #[default]
+    Never,
+    /// Undo the action when the corner triggers again.
+    Toggle,
+    /// Undo the action when the pointer leaves the corner.
+    Leave,
+}
+
+/// How the pointer entered a corner.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Approach {
+    /// From the inside of the output.
+    FromInside,
+    /// Sliding along the border of the output.
+    AlongEdge,
+}
+
+/// Where the pointer went when it left a corner.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExitDirection {
+    /// Back toward the inside of the output.
+    Inward,
+    /// Sliding along the border of the output.
+    AlongEdge,
+    /// Off the output, onto a neighbouring one.
+    Outward,
+}
+
+impl fmt::Display for ExitDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExitDirection::Inward => "inward",
+            ExitDirection::AlongEdge => "along_edge",
+            ExitDirection::Outward => "outward",
+        })
+    }
+}
+
+/// A sequence of corners that runs a command when they are entered one after the other.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ChordConfig {
+    /// Name of the TOML table the chord was defined in.
+    #[serde(skip)]
+    pub name: String,
+    /// Names of the corners in the order they have to be entered.
+    pub sequence: Vec<String>,
+    /// Time in which all corners of the sequence have to be entered.
+    #[serde(default = "default_chord_window_ms")]
+    pub window_ms: u32,
+    pub command: CommandConfig,
+}
+
+fn default_chord_window_ms() -> u32 {
+    1000
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ZoneConfig {
+    /// Name of the zone passed to its commands, defaults to its position in the list.
+    pub name: Option<String>,
+    /// Where the zone starts along the edge, from the left or the top.
+    #[serde(default = "default_zone_start")]
+    pub start: Offset,
+    /// Where the zone ends along the edge, from the left or the top.
+    #[serde(default = "default_zone_end")]
+    pub end: Offset,
+    #[serde(default = "default_command", alias = "command")]
+    pub enter_command: CommandConfig,
+    #[serde(default = "default_command")]
+    pub exit_command: CommandConfig,
+}
+
+impl ZoneConfig {
+    /// Whether the zone does not start before it ends. Zones mixing pixels and percentages
+    /// depend on the length of the edge, so they are never considered empty.
+    fn is_empty(&self) -> bool {
+        match (self.start, self.end) {
+            (Offset::Pixels(start), Offset::Pixels(end)) => start >= end,
+            (Offset::Percent(start), Offset::Percent(end)) => start >= end,
+            _ => false,
+        }
+    }
+}
+
+fn default_zone_start() -> Offset {
+    Offset::Percent(0.0)
+}
+
+fn default_zone_end() -> Offset {
+    Offset::Percent(100.0)
+}
+
+/// Distance along an edge, either in pixels or in percent of the length of the edge.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Offset {
+    Pixels(u32),
+    Percent(f64),
+}
+
+impl Offset {
+    /// The distance in pixels on an edge of the given length.
+    pub fn resolve(&self, length: u32) -> f64 {
+        match self {
+            Offset::Pixels(value) => f64::from(*value),
+            Offset::Percent(value) => f64::from(length) * value / 100.0,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Offset {
+    fn deserialize<D>(deserializer: D) -> Result<Offset, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawOffset {
+            Pixels(u32),
+            Text(String),
+        }
+
+        let value = match RawOffset::deserialize(deserializer)? {
+            RawOffset::Pixels(value) => return Ok(Offset::Pixels(value)),
+            RawOffset::Text(value) => value,
+        };
+        let trimmed = value.trim();
+        let offset = if let Some(percent) = trimmed.strip_suffix('%') {
+            percent
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|percent| (0.0..=100.0).contains(percent))
+                .map(Offset::Percent)
+        } else {
+            trimmed
+                .strip_suffix("px")
+                .unwrap_or(trimmed)
+                .trim()
+                .parse::<u32>()
+                .ok()
+                .map(Offset::Pixels)
+        };
+        offset.ok_or_else(|| {
+            de::Error::invalid_value(
+                Unexpected::Str(&value),
+                &"a percentage like \"33%\" or a number of pixels like 200 or \"200px\"",
+            )
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum CommandConfig {
+    /// Run through `$SHELL -c`, or `/bin/sh -c` if `$SHELL` is not set.
+    Shell(String),
+    /// Run the binary with the arguments as is.
+    Argv(Vec<String>),
+}
+
+impl CommandConfig {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            CommandConfig::Shell(command) => command.trim().is_empty(),
+            CommandConfig::Argv(command) => command.is_empty(),
+        }
+    }
+
+    /// Whether the command refers to the variable, either as `{name}` placeholder or as
+    /// `WAYCORNER_NAME` environment variable.
+    pub fn mentions(&self, name: &str) -> bool {
+        let placeholder = format!("{{{}}}", name);
+        let variable = command::variable_name(name);
+        let mentions = |value: &String| value.contains(&placeholder) || value.contains(&variable);
+        match self {
+            CommandConfig::Shell(command) => mentions(command),
+            CommandConfig::Argv(command) => command.iter().any(mentions),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 pub struct OutputConfig {
     pub description: Option<String>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum Location {
     TopLeft,
@@ -97,10 +504,85 @@ pub enum Location {
     Bottom,
 }
 
-type Config = HashMap<String, CornerConfig>;
+impl Location {
+    pub fn is_edge(&self) -> bool {
+        matches!(
+            self,
+            Location::Left | Location::Right | Location::Top | Location::Bottom
+        )
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Location::TopLeft => "top_left",
+            Location::TopRight => "top_right",
+            Location::BottomRight => "bottom_right",
+            Location::BottomLeft => "bottom_left",
+            Location::Left => "left",
+            Location::Right => "right",
+            Location::Top => "top",
+            Location::Bottom => "bottom",
+        })
+    }
+}
+
+/// The corners and chords of the config file.
+#[derive(Debug)]
+pub struct Config {
+    pub corners: Vec<CornerConfig>,
+    pub chords: Vec<ChordConfig>,
+}
+
+/// The config file as written: the `chord` table holds the chords, all other tables are
+/// corners.
+#[derive(Default)]
+struct ConfigFile {
+    chord: HashMap<String, ChordConfig>,
+    corners: HashMap<String, CornerConfig>,
+}
+
+impl<'de> Deserialize<'de> for ConfigFile {
+    fn deserialize<D>(deserializer: D) -> Result<ConfigFile, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // Unlike `#[serde(flatten)]`, deserializing each table directly keeps the location of
+        // errors in the corners.
+        struct ConfigFileVisitor;
+
+        impl<'de> de::Visitor<'de> for ConfigFileVisitor {
+            type Value = ConfigFile;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("tables of corners and a `chord` table")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<ConfigFile, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut config_file = ConfigFile::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "chord" {
+                        config_file.chord = map.next_value()?;
+                    } else {
+                        let corner = map.next_value()?;
+                        config_file.corners.insert(key, corner);
+                    }
+                }
+                Ok(config_file)
+            }
+        }
+
+        deserializer.deserialize_map(ConfigFileVisitor)
+    }
+}
 
-pub fn get_configs(config_path: PathBuf) -> Result<Vec<CornerConfig>> {
-    let path = if config_path.starts_with("~/") {
+/// Expand the `~/` prefix of the config path.
+pub fn resolve_path(config_path: PathBuf) -> Result<PathBuf> {
+    Ok(if config_path.starts_with("~/") {
         debug!("Replacing ~/ with $HOME/");
         let home_path = env::var_os("HOME")
             .context("could not find the $HOME env var to use for the default config path")?;
@@ -112,24 +594,194 @@ pub fn get_configs(config_path: PathBuf) -> Result<Vec<CornerConfig>> {
         PathBuf::from(home_path).join(relative_path)
     } else {
         config_path
-    };
+    })
+}
+
+pub fn get_configs(path: &Path) -> Result<Config> {
     info!("Using config: {}", path.display());
-    let mut config_file = File::open(path.clone())
-        .with_context(|| format!("could not open the file {}", path.display()))?;
+    let mut config_file =
+        File::open(path).with_context(|| format!("could not open the file {}", path.display()))?;
     let mut config_content = String::new();
     config_file.read_to_string(&mut config_content)?;
-    toml::from_str::<Config>(config_content.as_str()).map(|item| {
-        item.into_iter()
-            .map(|(key, value)| {
-                if value.enter_command.is_empty() && value.exit_command.is_empty() {
-                    bail!(
-                        "You must provide either an `exit_command` or an `enter_command` for `{}`",
-                        key
-                    )
-                }
-                Ok(value)
-            })
-            .collect::<Result<Vec<_>>>()
-            .with_context(|| format!("could not parse {}", path.display()))
-    })?
+    let ConfigFile {
+        chord: chords,
+        corners,
+    } = toml::from_str::<ConfigFile>(config_content.as_str())
+        .with_context(|| format!("could not parse {}", path.display()))?;
+    let chords = chords
+        .into_iter()
+        .map(|(key, mut value)| {
+            if value.sequence.is_empty() {
+                bail!(
+                    "You must provide a `sequence` of corners for chord `{}`",
+                    key
+                )
+            }
+            if let Some(corner) = value
+                .sequence
+                .iter()
+                .find(|corner| !corners.contains_key(*corner))
+            {
+                bail!(
+                    "Unknown corner `{}` in the sequence of chord `{}`",
+                    corner,
+                    key
+                )
+            }
+            if value.command.is_empty() {
+                bail!("You must provide a `command` for chord `{}`", key)
+            }
+            value.name = key;
+            Ok(value)
+        })
+        .collect::<Result<Vec<_>>>()
+        .with_context(|| format!("could not parse {}", path.display()))?;
+    let corners = corners
+        .into_iter()
+        .map(|(key, mut value)| {
+            // Corners can be used only as a part of chords.
+            let in_chord = chords.iter().any(|chord| chord.sequence.contains(&key));
+            if !value.has_commands() && !in_chord {
+                bail!(
+                    "You must provide at least one command, like an `exit_command` or an `enter_command`, for `{}`",
+                    key
+                )
+            }
+            if let Some(button) = value
+                .button_commands
+                .keys()
+                .find(|button| !BUTTONS.iter().any(|(name, _)| name == button))
+            {
+                bail!(
+                    "Unknown button `{}` in `button_commands` for `{}`, expected one of: {}",
+                    button,
+                    key,
+                    BUTTONS.map(|(name, _)| name).join(", ")
+                )
+            }
+            if value.while_dragging() == WhileDragging::Command && value.drag_command.is_empty() {
+                bail!(
+                    "You must provide a `drag_command` when `while_dragging` is `command`, for `{}`",
+                    key
+                )
+            }
+            if !value.zones.is_empty()
+                && value.locations.iter().any(|location| !location.is_edge())
+            {
+                bail!("Zones are only supported on edge locations, for `{}`", key)
+            }
+            if !value.slider_command.is_empty()
+                && value.locations.iter().any(|location| !location.is_edge())
+            {
+                bail!(
+                    "The `slider_command` is only supported on edge locations, for `{}`",
+                    key
+                )
+            }
+            if let Some((index, zone)) = value
+                .zones
+                .iter()
+                .enumerate()
+                .find(|(_, zone)| zone.is_empty())
+            {
+                bail!(
+                    "The `start` of zone `{}` must be before its `end`, for `{}`",
+                    zone.name.clone().unwrap_or_else(|| index.to_string()),
+                    key
+                )
+            }
+            value.name = key;
+            Ok(value)
+        })
+        .collect::<Result<Vec<_>>>()
+        .with_context(|| format!("could not parse {}", path.display()))?;
+    Ok(Config { corners, chords })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_file_tables() {
+        let config_file = toml::from_str::<ConfigFile>(
+            r#"
+            [left]
+            enter_command = "true"
+
+            [chord.both]
+            sequence = ["left", "left"]
+            command = "true"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config_file.corners.keys().collect::<Vec<_>>(), ["left"]);
+        assert_eq!(config_file.chord["both"].sequence, ["left", "left"]);
+    }
+
+    /// Deserialize the `start` of a zone.
+    fn offset(value: &str) -> Result<Offset, toml::de::Error> {
+        #[derive(Deserialize)]
+        struct Zone {
+            start: Offset,
+        }
+
+        toml::from_str::<Zone>(&format!("start = {}", value)).map(|zone| zone.start)
+    }
+
+    #[test]
+    fn offset_values() {
+        assert_eq!(offset("200").unwrap(), Offset::Pixels(200));
+        assert_eq!(offset("\"200\"").unwrap(), Offset::Pixels(200));
+        assert_eq!(offset("\"200px\"").unwrap(), Offset::Pixels(200));
+        assert_eq!(offset("\" 200 px \"").unwrap(), Offset::Pixels(200));
+        assert_eq!(offset("\"33%\"").unwrap(), Offset::Percent(33.0));
+        assert_eq!(offset("\"12.5 %\"").unwrap(), Offset::Percent(12.5));
+        assert_eq!(offset("\"100%\"").unwrap(), Offset::Percent(100.0));
+    }
+
+    #[test]
+    fn offset_invalid_values() {
+        for value in [
+            "-1",
+            "1.5",
+            "\"\"",
+            "\"%\"",
+            "\"101%\"",
+            "\"-1%\"",
+            "\"1.5px\"",
+            "\"200em\"",
+        ] {
+            assert!(offset(value).is_err(), "{} should be invalid", value);
+        }
+    }
+
+    #[test]
+    fn offset_resolve() {
+        assert_eq!(Offset::Pixels(200).resolve(1000), 200.0);
+        assert_eq!(Offset::Percent(33.0).resolve(1000), 330.0);
+    }
+
+    #[test]
+    fn empty_zones() {
+        let zone = |start, end| ZoneConfig {
+            name: None,
+            start,
+            end,
+            enter_command: default_command(),
+            exit_command: default_command(),
+        };
+        assert!(zone(Offset::Pixels(200), Offset::Pixels(200)).is_empty());
+        assert!(zone(Offset::Percent(50.0), Offset::Percent(10.0)).is_empty());
+        assert!(!zone(Offset::Percent(0.0), Offset::Percent(33.0)).is_empty());
+        assert!(!zone(Offset::Percent(50.0), Offset::Pixels(10)).is_empty());
+    }
+
+    #[test]
+    fn config_file_error_location() {
+        let content = "[left]\nenter_command = \"true\"\ntimeout_ms = \"slow\"\n";
+        let error = toml::from_str::<ConfigFile>(content).err().unwrap();
+        let start = content.find("\"slow\"").unwrap();
+        assert_eq!(error.span(), Some(start..start + 6));
+    }
 }
@@ -1,24 +1,611 @@
 use std::{
     borrow::Borrow,
     cmp,
+    collections::HashMap,
     process::Command,
     sync::{
+        atomic::{AtomicBool, Ordering},
         mpsc::{channel, Receiver, Sender},
         Arc, Mutex,
     },
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::Result;
 use regex::Regex;
 use tracing::{debug, info};
 
-use crate::config::CornerConfig;
+use crate::{
+    chord::Chords,
+    command::{self, Runner},
+    config::{
+        Action, Approach, ClickThrough, CommandConfig, CornerConfig, ExitDirection, Location,
+        Restore, WhileDragging,
+    },
+    events::{EventKind, Record, Subscribers},
+    selection::SelectionKind,
+};
+
+/// The surface an event happened on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Origin {
+    pub location: Location,
+    /// Description of the output the surface is on.
+    pub output: String,
+}
+
+/// Position of the pointer on a surface, in surface coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+    /// Width of the surface.
+    pub width: u32,
+    /// Height of the surface.
+    pub height: u32,
+}
+
+impl Position {
+    /// Map the position along an edge to a value from 0 to 100. The value grows from left to
+    /// right on horizontal edges and from bottom to top on vertical edges.
+    fn slider_value(&self, location: &Location) -> Option<u8> {
+        let ratio = |value: f64, length: u32| {
+            (length > 0).then(|| (value / f64::from(length)).clamp(0.0, 1.0))
+        };
+        let ratio = match location {
+            Location::Top | Location::Bottom => ratio(self.x, self.width)?,
+            Location::Left | Location::Right => 1.0 - ratio(self.y, self.height)?,
+            _ => return None,
+        };
+        Some((ratio * 100.0).round() as u8)
+    }
+
+    /// Distances from the position to the left, top, right and bottom side of the surface.
+    fn distances(&self) -> [f64; 4] {
+        [
+            self.x,
+            self.y,
+            f64::from(self.width) - self.x,
+            f64::from(self.height) - self.y,
+        ]
+    }
+
+    /// How far the position is from the sides of the surface that the pointer can cross, which
+    /// are the sides that do not lie on the border of the output.
+    fn depth(&self, border_sides: [bool; 4]) -> f64 {
+        self.distances()
+            .into_iter()
+            .zip(border_sides)
+            .filter(|(_, border)| !border)
+            .map(|(distance, _)| distance)
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// How the pointer entered the surface when this is the first position on it. It slid along
+    /// the border of the output when it entered right at a crossable side while touching the
+    /// border.
+    fn approach(&self, border_sides: [bool; 4]) -> Approach {
+        if self.touches_border(border_sides) && self.depth(border_sides) < 1.0 {
+            Approach::AlongEdge
+        } else {
+            Approach::FromInside
+        }
+    }
+
+    /// Where the pointer went when this is the last position on the surface. The side it left
+    /// through is the one it was heading to coming from the previous position, or the closest
+    /// one if it did not move on the surface.
+    fn exit_direction(
+        &self,
+        previous: Option<&Position>,
+        border_sides: [bool; 4],
+    ) -> ExitDirection {
+        let distances = self.distances();
+        let (dx, dy) = previous.map_or((0.0, 0.0), |previous| {
+            (self.x - previous.x, self.y - previous.y)
+        });
+        let speeds = [-dx, -dy, dx, dy];
+        let side = if speeds.iter().any(|speed| *speed > 0.0) {
+            (0..distances.len())
+                .filter(|side| speeds[*side] > 0.0)
+                .min_by(|a, b| {
+                    (distances[*a] / speeds[*a]).total_cmp(&(distances[*b] / speeds[*b]))
+                })
+        } else {
+            (0..distances.len()).min_by(|a, b| distances[*a].total_cmp(&distances[*b]))
+        }
+        .unwrap_or_default();
+        if border_sides[side] {
+            ExitDirection::Outward
+        } else if self.touches_border(border_sides) {
+            ExitDirection::AlongEdge
+        } else {
+            ExitDirection::Inward
+        }
+    }
+
+    fn touches_border(&self, border_sides: [bool; 4]) -> bool {
+        self.distances()
+            .into_iter()
+            .zip(border_sides)
+            .any(|(distance, border)| border && distance < 1.0)
+    }
+}
+
+/// Which sides of the surface of the location lie on the border of the output, in the order of
+/// [`Position::distances`]. The ends of edges only do so when there is no margin.
+fn border_sides(location: &Location, margin: i8) -> [bool; 4] {
+    let ends = margin <= 0;
+    match location {
+        Location::TopLeft => [true, true, false, false],
+        Location::TopRight => [false, true, true, false],
+        Location::BottomRight => [false, false, true, true],
+        Location::BottomLeft => [true, false, false, true],
+        Location::Top => [ends, true, ends, false],
+        Location::Bottom => [ends, false, ends, true],
+        Location::Left => [true, ends, false, ends],
+        Location::Right => [false, ends, true, ends],
+    }
+}
+
+/// How long the motion of the pointer is followed after entering the corner to tell its
+/// velocity.
+const APPROACH_SAMPLE: Duration = Duration::from_millis(50);
+
+/// How long to wait for the text of a selection before running a command without it.
+const SELECTION_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The pointer entering the corner while its approach is not judged yet.
+struct Arrival {
+    origin: Origin,
+    received_at: Instant,
+    /// How deep into the surface the pointer entered.
+    depth: f64,
+    last: Option<Position>,
+    /// Distance the pointer moved on the surface since entering it.
+    moved: f64,
+    /// Times of the first and last motion on the surface.
+    times: Option<(u32, u32)>,
+    /// Distance the pointer moved between the first and last motion.
+    timed_moved: f64,
+}
+
+impl Arrival {
+    fn new(origin: Origin) -> Arrival {
+        Arrival {
+            origin,
+            received_at: Instant::now(),
+            depth: 0.0,
+            last: None,
+            moved: 0.0,
+            times: None,
+            timed_moved: 0.0,
+        }
+    }
+
+    /// Follow the motion of the pointer, returns the approach when it is the first position
+    /// of the pointer in the corner.
+    fn motion(
+        &mut self,
+        position: Position,
+        time: Option<u32>,
+        border_sides: [bool; 4],
+    ) -> Option<Approach> {
+        let approach = self.last.is_none().then(|| {
+            self.depth = position.depth(border_sides);
+            position.approach(border_sides)
+        });
+        let distance = self
+            .last
+            .map_or(0.0, |last| (position.x - last.x).hypot(position.y - last.y));
+        self.moved += distance;
+        if let Some(time) = time {
+            match self.times.as_mut() {
+                Some((_, last)) => {
+                    *last = time;
+                    self.timed_moved += distance;
+                }
+                None => self.times = Some((time, time)),
+            }
+        }
+        self.last = Some(position);
+        approach
+    }
+
+    /// When the velocity is judged, unless the motions spanned enough time before.
+    fn deadline(&self) -> Instant {
+        self.received_at + APPROACH_SAMPLE
+    }
+
+    fn is_due(&self, now: Instant) -> bool {
+        self.sampled() || self.deadline() <= now
+    }
+
+    /// Whether the motions span enough time to judge the velocity from.
+    fn sampled(&self) -> bool {
+        self.times.is_some_and(|(first, last)| {
+            u128::from(last.wrapping_sub(first)) >= APPROACH_SAMPLE.as_millis()
+        })
+    }
+
+    /// Velocity in pixels per second, from the times of the motions when there were enough.
+    /// When the pointer stopped right away, like against the border of the output, it is
+    /// estimated from how far it got since entering instead.
+    fn velocity(&self) -> f64 {
+        match self.times {
+            Some((first, last)) if last != first => {
+                self.timed_moved * 1000.0 / f64::from(last.wrapping_sub(first))
+            }
+            _ => (self.depth + self.moved) / self.received_at.elapsed().as_secs_f64(),
+        }
+    }
+}
+
+/// Requests from a corner to the Wayland event loop.
+#[derive(Debug)]
+pub enum CornerRequest {
+    /// Only let the sliver at the border of the output take input on the surface of the origin.
+    ClickThrough { corner: String, origin: Origin },
+    /// Run the built-in action of the corner on the output of the origin, or undo it when it
+    /// should be toggled.
+    RunAction {
+        corner: String,
+        action: Action,
+        origin: Origin,
+    },
+    /// Undo the built-in action of the corner.
+    RestoreAction { corner: String },
+    /// Read the text of the selection and send it back, or nothing if there is no selection.
+    ReadSelection {
+        kind: SelectionKind,
+        reply: Sender<String>,
+    },
+}
 
 #[derive(Debug, PartialEq)]
 pub enum CornerEvent {
-    Enter,
-    Leave,
+    Enter(Origin),
+    Leave(Origin),
+    /// A button was pressed, with the name of the button.
+    Button(Origin, &'static str),
+    /// A scroll step, with its direction: `up`, `down`, `left` or `right`.
+    Scroll(Origin, &'static str),
+    /// The pointer moved on the surface, or entered it at the position. Has the time of the
+    /// motion in milliseconds, which is not known for entering.
+    Motion(Origin, Position, Option<u32>),
+    /// Whether a button is held on the seat of the pointer.
+    Drag(bool),
+    /// A touch started on the surface and moved inward.
+    Swipe(Origin),
+    /// Files or text were dropped onto the surface, with the paths, URIs or text.
+    Drop(Origin, Vec<String>),
+    /// Stop waiting for events, sent when the corner is removed.
+    Stop,
+}
+
+/// The pointer staying in the corner, from entering it until leaving it.
+struct Dwell {
+    origin: Origin,
+    entered_at: Instant,
+    /// Index of the next stage to run.
+    next_stage: usize,
+    /// How many times the enter command ran while in the corner.
+    runs: u32,
+    /// When the enter command runs again, if it should repeat.
+    next_repeat: Option<Instant>,
+}
+
+/// Debounce state of a zone of an edge, which is entered and left like a corner.
+#[derive(Default)]
+struct ZoneState {
+    /// The last event and when its command should run if no other event arrives before.
+    pending: Option<(CornerEvent, Instant)>,
+    command_done_at: Option<Instant>,
+    entered_at: Option<Instant>,
+    dwell_time: Duration,
+}
+
+impl ZoneState {
+    fn handle(&mut self, event: CornerEvent, timeout: Duration) {
+        let now = Instant::now();
+        if self
+            .command_done_at
+            .is_some_and(|value| now.duration_since(value) < Duration::from_millis(250))
+        {
+            debug!("Ignored the zone event due to too fast after unlock.");
+            return;
+        }
+        if let CornerEvent::Leave(_) = event {
+            self.dwell_time = self
+                .entered_at
+                .take()
+                .map(|value| value.elapsed())
+                .unwrap_or_default();
+        } else {
+            self.entered_at = Some(now);
+        }
+        self.pending = Some((event, now + timeout));
+    }
+
+    /// Take the pending event once its command should run.
+    fn take_due(&mut self, now: Instant) -> Option<CornerEvent> {
+        self.pending
+            .take_if(|(_, at)| *at <= now)
+            .map(|(event, _)| event)
+    }
+
+    /// How long the pointer has been in the zone.
+    fn dwell(&self) -> Duration {
+        self.entered_at
+            .map(|value| value.elapsed())
+            .unwrap_or_default()
+    }
+}
+
+/// The zones of an edge and the one the pointer is in.
+struct Zones {
+    states: Vec<ZoneState>,
+    current: Option<usize>,
+}
+
+impl Zones {
+    fn new(count: usize) -> Zones {
+        Zones {
+            states: (0..count).map(|_| ZoneState::default()).collect(),
+            current: None,
+        }
+    }
+
+    fn clear_pending(&mut self) {
+        self.states.iter_mut().for_each(|zone| zone.pending = None);
+    }
+
+    /// The pointer moved into the zone, or out of all zones.
+    fn move_to(&mut self, zone: Option<usize>, origin: &Origin, timeout: Duration) {
+        if zone == self.current {
+            return;
+        }
+        self.leave(origin, timeout);
+        if let Some(index) = zone {
+            self.states[index].handle(CornerEvent::Enter(origin.clone()), timeout);
+        }
+        self.current = zone;
+    }
+
+    /// The pointer left the zone it was in.
+    fn leave(&mut self, origin: &Origin, timeout: Duration) {
+        if let Some(index) = self.current.take() {
+            self.states[index].handle(CornerEvent::Leave(origin.clone()), timeout);
+        }
+    }
+
+    fn deadlines(&self) -> impl Iterator<Item = Option<Instant>> + '_ {
+        self.states
+            .iter()
+            .map(|zone| zone.pending.as_ref().map(|(_, at)| *at))
+    }
+}
+
+/// The series of times the corner was entered, for corners that need several hits.
+struct Hits {
+    needed: usize,
+    window: Duration,
+    /// When the corner was entered since the first hit of the series.
+    entered: Vec<Instant>,
+    /// Where the pointer stayed in the corner without enough hits to run the enter command.
+    single_hit: Option<Origin>,
+}
+
+impl Hits {
+    fn new(needed: u8, window: Duration) -> Hits {
+        Hits {
+            needed: needed.into(),
+            window,
+            entered: vec![],
+            single_hit: None,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entered.clear();
+        self.single_hit = None;
+    }
+
+    fn hit(&mut self) {
+        if self.needed > 1 {
+            // Start a new series when the previous one reached enough hits but the pointer did
+            // not stay in the corner.
+            if self
+                .entered
+                .first()
+                .is_some_and(|at| at.elapsed() >= self.window)
+            {
+                self.entered.clear();
+            }
+            self.entered.push(Instant::now());
+        }
+    }
+
+    /// How many more hits are needed to run the enter command.
+    fn missing(&self) -> usize {
+        if self.needed > 1 {
+            self.needed.saturating_sub(self.entered.len())
+        } else {
+            0
+        }
+    }
+
+    /// When the window of the series passes without enough hits.
+    fn deadline(&self) -> Option<Instant> {
+        self.entered
+            .first()
+            .filter(|_| self.entered.len() < self.needed)
+            .map(|at| *at + self.window)
+    }
+
+    /// End the series once its window passed. Returns where the pointer stayed in the corner
+    /// if it did so for a single hit.
+    fn expire(&mut self, now: Instant) -> Option<Origin> {
+        if self.deadline().is_none_or(|at| at > now) {
+            return None;
+        }
+        debug!("Hit window passed with {} hits", self.entered.len());
+        self.entered.clear();
+        self.single_hit.take()
+    }
+}
+
+/// The values of the slider of an edge, passed to the slider command at most once per
+/// interval.
+struct Slider {
+    interval: Duration,
+    /// The value that still has to be passed to the slider command.
+    pending: Option<(Origin, u8)>,
+    last: Option<(u8, Instant)>,
+}
+
+impl Slider {
+    fn new(interval: Duration) -> Slider {
+        Slider {
+            interval,
+            pending: None,
+            last: None,
+        }
+    }
+
+    /// Take the pending value if the interval passed and it differs from the last one.
+    fn take_due(&mut self) -> Option<(Origin, u8)> {
+        if self
+            .last
+            .is_some_and(|(_, at)| at.elapsed() < self.interval)
+        {
+            return None;
+        }
+        self.pending
+            .take()
+            .filter(|(_, value)| self.last.is_none_or(|(last_value, _)| last_value != *value))
+    }
+
+    fn ran(&mut self, value: u8) {
+        self.last = Some((value, Instant::now()));
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.pending
+            .as_ref()
+            .and(self.last)
+            .map(|(_, at)| at + self.interval)
+    }
+}
+
+/// What caused a command to be executed, passed to the command as `WAYCORNER_*` environment
+/// variables and as `{...}` placeholders in its arguments.
+struct CommandContext<'a> {
+    origin: &'a Origin,
+    /// Either `enter`, `exit` or `stage`.
+    event: &'static str,
+    /// How long the pointer has been in the corner.
+    dwell: Duration,
+    /// How many times the enter command was repeated before this run.
+    repeat: u32,
+    /// Variables specific to the event.
+    extra: Vec<(&'static str, String)>,
+    /// Arguments appended to the command, available as `"$@"` in shell commands.
+    arguments: Vec<String>,
+    /// Whether the corner ignores events for a moment after the command exited.
+    locks: bool,
+}
+
+impl<'a> CommandContext<'a> {
+    fn new(origin: &'a Origin, event: &'static str, dwell: Duration) -> CommandContext<'a> {
+        CommandContext {
+            origin,
+            event,
+            dwell,
+            repeat: 0,
+            extra: vec![],
+            arguments: vec![],
+            locks: false,
+        }
+    }
+
+    fn variables(&self, name: &str) -> Vec<(&'static str, String)> {
+        let mut variables = vec![
+            ("name", name.to_owned()),
+            ("location", self.origin.location.to_string()),
+            ("output", self.origin.output.clone()),
+            ("event", self.event.to_owned()),
+            ("dwell_ms", self.dwell.as_millis().to_string()),
+            ("repeat", self.repeat.to_string()),
+        ];
+        variables.extend(self.extra.iter().cloned());
+        variables
+    }
+}
+
+/// Replace the `{key}` placeholders in the value with their variable, unknown placeholders are
+/// left untouched.
+fn substitute(value: &str, variables: &[(&str, String)]) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let candidate = &rest[start..];
+        let replacement = candidate.find('}').and_then(|end| {
+            variables
+                .iter()
+                .find(|(key, _)| *key == &candidate[1..end])
+                .map(|(_, variable)| (end, variable))
+        });
+        match replacement {
+            Some((end, variable)) => {
+                result.push_str(variable);
+                rest = &candidate[end + 1..];
+            }
+            None => {
+                result.push('{');
+                rest = &candidate[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// The program and arguments to run the command with. The placeholders of string commands
+/// become references to their environment variable, so their values never become part of the
+/// script.
+fn command_line(
+    command: &CommandConfig,
+    env: &HashMap<String, String>,
+    variables: &[(&str, String)],
+    arguments: &[String],
+) -> Vec<String> {
+    match command {
+        CommandConfig::Shell(command) if !command.trim().is_empty() => {
+            let references = variables
+                .iter()
+                .map(|(key, _)| (*key, command::shell_variable(key)))
+                .collect::<Vec<_>>();
+            let mut command = vec![
+                command::shell(),
+                "-c".to_owned(),
+                substitute(command, &references),
+            ];
+            if !arguments.is_empty() {
+                // The shell sets `$0` to the first argument after the command.
+                command.push("waycorner".to_owned());
+                command.extend(arguments.iter().cloned());
+            }
+            command
+        }
+        CommandConfig::Shell(_) => vec![],
+        CommandConfig::Argv(command) => command
+            .iter()
+            .map(|value| substitute(&command::expand(value, env), variables))
+            .chain(arguments.iter().cloned())
+            .collect(),
+    }
 }
 
 #[allow(clippy::type_complexity)]
@@ -29,71 +616,597 @@ pub struct Corner {
         Arc<Mutex<Sender<CornerEvent>>>,
         Arc<Mutex<Receiver<CornerEvent>>>,
     ),
+    enabled: AtomicBool,
+    subscribers: Arc<Subscribers>,
+    chords: Arc<Chords>,
+    requests: calloop::channel::Sender<CornerRequest>,
 }
 
 impl Corner {
-    pub fn new(config: CornerConfig) -> Corner {
+    pub fn new(
+        config: CornerConfig,
+        subscribers: Arc<Subscribers>,
+        chords: Arc<Chords>,
+        requests: calloop::channel::Sender<CornerRequest>,
+    ) -> Corner {
         let (tx, rx) = channel();
         Corner {
             config,
             channel: (Arc::new(Mutex::new(tx)), Arc::new(Mutex::new(rx))),
+            enabled: AtomicBool::new(true),
+            subscribers,
+            chords,
+            requests,
         }
     }
 
-    pub fn wait(&self) -> Result<()> {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        info!(
+            "{} corner {}",
+            if enabled { "Enabling" } else { "Disabling" },
+            self.config.name
+        );
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn wait(&self) {
         let timeout = Duration::from_millis(cmp::max(self.config.timeout_ms.into(), 5));
-        let mut last_event = None;
+        let mut stages = self.config.stages.iter().collect::<Vec<_>>();
+        stages.sort_by_key(|stage| stage.after_ms);
+        let repeat = self
+            .config
+            .repeat_ms
+            .map(|value| Duration::from_millis(cmp::max(value, 5).into()));
+        // The last event and when its command should run if no other event arrives before.
+        let mut pending: Option<(CornerEvent, Instant)> = None;
+        let mut dwell: Option<Dwell> = None;
         let mut command_done_at = None;
+        let mut dwell_time = Duration::ZERO;
+        // The last two positions of the pointer on the surface and where it went when it left.
+        let mut last_positions: [Option<Position>; 2] = [None, None];
+        let mut exit_direction: Option<ExitDirection> = None;
+        let mut slider = Slider::new(Duration::from_millis(self.config.slider_interval_ms.into()));
+        let mut arrival: Option<Arrival> = None;
+        // The enter event of an arrival that passed the approach filters.
+        let mut arrived: Option<CornerEvent> = None;
+        // Whether the last enter event was vetoed, so its leave event is ignored as well.
+        let mut vetoed = false;
+        // Whether a button is held, like when dragging a window into the corner.
+        let mut dragging = false;
+        let mut hits = Hits::new(
+            self.config.hits,
+            Duration::from_millis(self.config.hit_window_ms.into()),
+        );
+        let mut zones = Zones::new(self.config.zones.len());
+        let mut runner = Runner::new(
+            self.config.on_busy,
+            self.config
+                .command_timeout_ms
+                .map(|value| Duration::from_millis(value.into())),
+        );
         loop {
-            let event_result = self
-                .channel
-                .1
-                .lock()
-                .expect("cannot get corner receiver")
-                .recv_timeout(timeout);
+            if runner.poll() {
+                command_done_at = Some(Instant::now());
+            }
+
+            let now = Instant::now();
+            if !self.is_enabled() {
+                pending = None;
+                dwell = None;
+                zones.clear_pending();
+                hits.clear();
+            }
+
+            if pending.as_ref().is_some_and(|(_, at)| *at <= now) {
+                let ran_command = match pending.take() {
+                    Some((CornerEvent::Enter(_), _))
+                        if dragging && self.config.while_dragging() == WhileDragging::Ignore =>
+                    {
+                        debug!("Ignored the corner as a button is held");
+                        hits.clear();
+                        false
+                    }
+                    Some((CornerEvent::Enter(origin), _)) if hits.missing() > 0 => {
+                        debug!("Waiting for {} more hits", hits.missing());
+                        hits.single_hit = Some(origin);
+                        false
+                    }
+                    Some((CornerEvent::Enter(origin), _)) => {
+                        hits.clear();
+                        let drag_command =
+                            dragging && self.config.while_dragging() == WhileDragging::Command;
+                        let (command, event) = if drag_command {
+                            (&self.config.drag_command, "drag")
+                        } else {
+                            (&self.config.enter_command, "enter")
+                        };
+                        self.publish(EventKind::Triggered, &origin);
+                        self.click_through(&origin);
+                        if let Some(action) = self.config.action.filter(|_| !drag_command) {
+                            self.requests
+                                .send(CornerRequest::RunAction {
+                                    corner: self.config.name.clone(),
+                                    action,
+                                    origin: origin.clone(),
+                                })
+                                .ok();
+                        }
+                        let mut context = CommandContext::new(
+                            &origin,
+                            event,
+                            dwell
+                                .as_ref()
+                                .map(|dwell| dwell.entered_at.elapsed())
+                                .unwrap_or_default(),
+                        );
+                        context.locks = true;
+                        self.execute_command(&mut runner, command, &context);
+                        if let Some(dwell) = dwell.as_mut() {
+                            dwell.runs = 1;
+                            dwell.next_repeat = repeat
+                                .filter(|_| !drag_command && self.config.repeat_limit != Some(0))
+                                .map(|repeat| now + repeat);
+                        }
+                        true
+                    }
+                    Some((CornerEvent::Leave(_), _))
+                        if exit_direction.is_some_and(|direction| {
+                            !self.config.exit_directions.is_empty()
+                                && !self.config.exit_directions.contains(&direction)
+                        }) =>
+                    {
+                        debug!(
+                            "Not running the exit command for leaving {:?}",
+                            exit_direction
+                        );
+                        false
+                    }
+                    Some((CornerEvent::Leave(origin), _)) => {
+                        let mut context = CommandContext::new(&origin, "exit", dwell_time);
+                        context.extra.push((
+                            "direction",
+                            exit_direction
+                                .map(|direction| direction.to_string())
+                                .unwrap_or_default(),
+                        ));
+                        context.locks = true;
+                        self.execute_command(&mut runner, &self.config.exit_command, &context);
+                        // Nothing ran, so the next hit is not ignored.
+                        !self.config.exit_command.is_empty()
+                    }
+                    _ => true,
+                };
+                if ran_command {
+                    command_done_at = Some(now);
+                }
+            }
+
+            if let Some(origin) = hits.expire(now) {
+                if !self.config.single_hit_command.is_empty() {
+                    self.publish(EventKind::Triggered, &origin);
+                    self.execute_command(
+                        &mut runner,
+                        &self.config.single_hit_command,
+                        &CommandContext::new(&origin, "single_hit", Duration::ZERO),
+                    );
+                    command_done_at = Some(now);
+                }
+            }
+
+            // Stages and repeats only run while dragging if the corner should trigger anyway.
+            let drag_suppressed =
+                dragging && self.config.while_dragging() != WhileDragging::Trigger;
+            if let Some(dwell) = dwell.as_mut() {
+                while let Some(stage) = stages.get(dwell.next_stage).filter(|stage| {
+                    dwell.entered_at + Duration::from_millis(stage.after_ms.into()) <= now
+                }) {
+                    dwell.next_stage += 1;
+                    if drag_suppressed {
+                        debug!("Skipped a stage as a button is held");
+                        continue;
+                    }
+                    self.publish(EventKind::Triggered, &dwell.origin);
+                    self.click_through(&dwell.origin);
+                    let mut context =
+                        CommandContext::new(&dwell.origin, "stage", dwell.entered_at.elapsed());
+                    // Only lock the corner once the command exited, a stage may run while the
+                    // enter event is still pending.
+                    context.locks = true;
+                    self.execute_command(&mut runner, &stage.command, &context);
+                }
+
+                if drag_suppressed {
+                    dwell.next_repeat = None;
+                }
+                if dwell.next_repeat.is_some_and(|at| at <= now) {
+                    let mut context =
+                        CommandContext::new(&dwell.origin, "enter", dwell.entered_at.elapsed());
+                    context.repeat = dwell.runs;
+                    self.execute_command(&mut runner, &self.config.enter_command, &context);
+                    dwell.runs += 1;
+                    let limit_reached = self
+                        .config
+                        .repeat_limit
+                        .is_some_and(|limit| dwell.runs > limit);
+                    dwell.next_repeat =
+                        repeat.filter(|_| !limit_reached).map(|repeat| now + repeat);
+                }
+            }
+
+            let zone_states = self.config.zones.iter().zip(&mut zones.states);
+            for (index, (zone, state)) in zone_states.enumerate() {
+                let Some(event) = state.take_due(now) else {
+                    continue;
+                };
+                let name = zone.name.clone().unwrap_or_else(|| index.to_string());
+                match event {
+                    CornerEvent::Enter(origin) => {
+                        self.publish(EventKind::Triggered, &origin);
+                        let mut context = CommandContext::new(&origin, "enter", state.dwell());
+                        context.extra.push(("zone", name));
+                        self.execute_command(&mut runner, &zone.enter_command, &context);
+                    }
+                    CornerEvent::Leave(origin) => {
+                        let mut context = CommandContext::new(&origin, "exit", state.dwell_time);
+                        context.extra.push(("zone", name));
+                        self.execute_command(&mut runner, &zone.exit_command, &context);
+                    }
+                    _ => (),
+                }
+                state.command_done_at = Some(now);
+            }
+
+            if let Some(arrival) = arrival.take_if(|arrival| arrival.is_due(now)) {
+                let velocity = arrival.velocity();
+                debug!("Entered the corner at {:.0} pixels per second", velocity);
+                if self
+                    .config
+                    .min_velocity
+                    .is_some_and(|min_velocity| velocity < f64::from(min_velocity))
+                {
+                    info!("Ignoring the corner as the pointer entered it too slowly");
+                    self.publish(EventKind::Cancelled, &arrival.origin);
+                    vetoed = true;
+                } else {
+                    arrived = Some(CornerEvent::Enter(arrival.origin));
+                }
+            }
+
+            if let Some((origin, value)) = slider.take_due() {
+                let mut context = CommandContext::new(&origin, "slider", Duration::ZERO);
+                context.extra.push(("value", value.to_string()));
+                self.execute_command(&mut runner, &self.config.slider_command, &context);
+                slider.ran(value);
+            }
+
+            let stage_deadline = dwell.as_ref().and_then(|dwell| {
+                stages
+                    .get(dwell.next_stage)
+                    .map(|stage| dwell.entered_at + Duration::from_millis(stage.after_ms.into()))
+            });
+            let repeat_deadline = dwell.as_ref().and_then(|dwell| dwell.next_repeat);
+            let deadline = [
+                pending.as_ref().map(|(_, at)| *at),
+                stage_deadline,
+                repeat_deadline,
+                slider.deadline(),
+                hits.deadline(),
+                arrival.as_ref().map(Arrival::deadline),
+            ]
+            .into_iter()
+            .chain(zones.deadlines())
+            .flatten()
+            .min();
+            let (event_result, judged) = match arrived.take() {
+                Some(event) => (Ok(event), true),
+                None => (
+                    self.channel
+                        .1
+                        .lock()
+                        .expect("cannot get corner receiver")
+                        .recv_timeout(deadline.map_or(timeout, |value| {
+                            value.saturating_duration_since(Instant::now())
+                        })),
+                    false,
+                ),
+            };
             match event_result {
+                Ok(CornerEvent::Stop) => {
+                    debug!("Stopping corner");
+                    return;
+                }
+                Ok(CornerEvent::Button(origin, button)) => {
+                    debug!("Received button: {}", button);
+                    self.run_action(
+                        &mut runner,
+                        self.config.button_commands.get(button),
+                        &origin,
+                        &dwell,
+                        ("button", button),
+                    );
+                }
+                Ok(CornerEvent::Scroll(origin, direction)) => {
+                    debug!("Received scroll: {}", direction);
+                    self.run_action(
+                        &mut runner,
+                        Some(self.config.scroll_command(direction)),
+                        &origin,
+                        &dwell,
+                        ("scroll", direction),
+                    );
+                }
+                Ok(CornerEvent::Enter(origin))
+                    if !judged && self.is_enabled() && self.config.filters_approach() =>
+                {
+                    debug!("Judging the approach of the pointer");
+                    vetoed = false;
+                    last_positions = [None, None];
+                    arrival = Some(Arrival::new(origin));
+                }
+                Ok(CornerEvent::Leave(_)) if vetoed || arrival.is_some() => {
+                    debug!("Ignored leaving the corner as entering it was ignored.");
+                    vetoed = false;
+                    arrival = None;
+                }
+                Ok(CornerEvent::Drag(value)) => {
+                    debug!("Dragging: {}", value);
+                    dragging = value;
+                }
+                Ok(CornerEvent::Swipe(origin)) => {
+                    debug!("Received swipe");
+                    if !self.is_enabled() {
+                        debug!("Ignored the swipe as the corner is disabled.");
+                    } else {
+                        self.execute_command(
+                            &mut runner,
+                            &self.config.swipe_command,
+                            &CommandContext::new(&origin, "swipe", Duration::ZERO),
+                        );
+                    }
+                }
+                Ok(CornerEvent::Drop(origin, items)) => {
+                    debug!("Received drop: {:?}", items);
+                    if !self.is_enabled() {
+                        debug!("Ignored the drop as the corner is disabled.");
+                    } else if !self.config.drop_command.is_empty() {
+                        let mut context = CommandContext::new(&origin, "drop", Duration::ZERO);
+                        context.extra.push(("count", items.len().to_string()));
+                        context.arguments = items;
+                        self.execute_command(&mut runner, &self.config.drop_command, &context);
+                    }
+                }
+                Ok(CornerEvent::Motion(origin, position, time)) => {
+                    last_positions = [last_positions[1], Some(position)];
+                    if !self.is_enabled() {
+                        continue;
+                    }
+                    if let Some(current) = arrival.as_mut() {
+                        let border_sides = border_sides(&origin.location, self.config.margin);
+                        let approach = current.motion(position, time, border_sides);
+                        if let Some(approach) = approach {
+                            debug!("Entered the corner {:?}", approach);
+                        }
+                        let wrong_approach = approach.is_some_and(|approach| {
+                            !self.config.approach.is_empty()
+                                && !self.config.approach.contains(&approach)
+                        });
+                        if wrong_approach {
+                            info!("Ignoring the corner as the pointer entered it from the wrong direction");
+                            self.publish(EventKind::Cancelled, &current.origin);
+                            arrival = None;
+                            vetoed = true;
+                        }
+                    }
+                    if !self.config.zones.is_empty() {
+                        let zone = self.zone_at(&origin.location, &position);
+                        zones.move_to(zone, &origin, timeout);
+                    }
+                    if !self.config.slider_command.is_empty() {
+                        if let Some(value) = position.slider_value(&origin.location) {
+                            slider.pending = Some((origin, value));
+                        }
+                    }
+                }
                 Ok(event) => {
                     debug!("Received event: {:?}", event);
-                    if command_done_at.map_or(true, |value| {
+                    if let (CornerEvent::Enter(_), false) = (&event, judged) {
+                        last_positions = [None, None];
+                    }
+                    if let (CornerEvent::Enter(origin), true) = (&event, self.is_enabled()) {
+                        if let Some(chord) = self.chords.enter(&self.config.name) {
+                            let mut context = CommandContext::new(origin, "chord", Duration::ZERO);
+                            context.extra.push(("chord", chord.name));
+                            self.execute_command(&mut runner, &chord.command, &context);
+                            command_done_at = Some(Instant::now());
+                        }
+                    }
+                    if let CornerEvent::Leave(origin) = &event {
+                        zones.leave(origin, timeout);
+                        // Restore right away, even when the leave event itself is ignored.
+                        if self.config.action.is_some() && self.config.restore == Restore::Leave {
+                            self.requests
+                                .send(CornerRequest::RestoreAction {
+                                    corner: self.config.name.clone(),
+                                })
+                                .ok();
+                        }
+                    }
+                    if !self.is_enabled() {
+                        debug!("Ignored the event as the corner is disabled.");
+                        pending = None;
+                        dwell = None;
+                    } else if command_done_at.is_none_or(|value| {
                         Instant::now()
                             .duration_since(value)
                             .ge(&Duration::from_millis(250))
                     }) {
-                        last_event = Some(event);
+                        match &event {
+                            CornerEvent::Enter(origin) => {
+                                self.publish(EventKind::Enter, origin);
+                                hits.hit();
+                                dwell = Some(Dwell {
+                                    origin: origin.clone(),
+                                    entered_at: Instant::now(),
+                                    next_stage: 0,
+                                    runs: 0,
+                                    next_repeat: None,
+                                });
+                            }
+                            CornerEvent::Leave(origin) => {
+                                self.publish(EventKind::Leave, origin);
+                                let enter_pending =
+                                    matches!(pending, Some((CornerEvent::Enter(_), _)));
+                                let stages_pending = dwell
+                                    .as_ref()
+                                    .is_some_and(|dwell| dwell.next_stage < stages.len());
+                                if enter_pending || stages_pending {
+                                    self.publish(EventKind::Cancelled, origin);
+                                }
+                                dwell_time = dwell
+                                    .take()
+                                    .map(|dwell| dwell.entered_at.elapsed())
+                                    .unwrap_or_default();
+                                let [previous, last] = last_positions;
+                                exit_direction = last.map(|last| {
+                                    last.exit_direction(
+                                        previous.as_ref(),
+                                        border_sides(&origin.location, self.config.margin),
+                                    )
+                                });
+                            }
+                            CornerEvent::Button(..)
+                            | CornerEvent::Scroll(..)
+                            | CornerEvent::Motion(..)
+                            | CornerEvent::Drag(_)
+                            | CornerEvent::Swipe(_)
+                            | CornerEvent::Drop(..)
+                            | CornerEvent::Stop => (),
+                        }
+                        pending = Some((event, Instant::now() + timeout));
                     } else {
                         debug!("Ignored the event due to too fast after unlock.");
-                    }
-                }
-                Err(_error) => {
-                    if let Some(event) = last_event {
-                        if event == CornerEvent::Enter {
-                            self.execute_command(&self.config.enter_command)?;
-                        } else if event == CornerEvent::Leave {
-                            self.execute_command(&self.config.exit_command)?;
+                        if let CornerEvent::Leave(_) = event {
+                            // Never run the remaining stages or a pending enter once the pointer
+                            // left.
+                            dwell = None;
+                            pending.take_if(|(event, _)| matches!(event, CornerEvent::Enter(_)));
                         }
-                        command_done_at = Some(Instant::now());
                     }
-                    last_event = None;
                 }
+                Err(_) => (),
             }
         }
     }
 
-    pub fn on_enter_mouse(&self) -> Result<()> {
-        self.channel
-            .0
-            .lock()
-            .expect("Cannot get sender")
-            .send(CornerEvent::Enter)?;
-        Ok(())
+    /// Immediately run the command of an action like a button press, the event and the variable
+    /// passed to the command are named after the action.
+    fn run_action(
+        &self,
+        runner: &mut Runner,
+        command: Option<&CommandConfig>,
+        origin: &Origin,
+        dwell: &Option<Dwell>,
+        (action, value): (&'static str, &str),
+    ) {
+        let command = match command {
+            Some(command) if !command.is_empty() => command,
+            _ => return,
+        };
+        if !self.is_enabled() {
+            debug!("Ignored the {} as the corner is disabled.", action);
+            return;
+        }
+        let mut context = CommandContext::new(
+            origin,
+            action,
+            dwell
+                .as_ref()
+                .map(|dwell| dwell.entered_at.elapsed())
+                .unwrap_or_default(),
+        );
+        context.extra.push((action, value.to_owned()));
+        self.execute_command(runner, command, &context);
+    }
+
+    /// The first zone that contains the position along the edge.
+    fn zone_at(&self, location: &Location, position: &Position) -> Option<usize> {
+        let (offset, length) = match location {
+            Location::Top | Location::Bottom => (position.x, position.width),
+            Location::Left | Location::Right => (position.y, position.height),
+            _ => return None,
+        };
+        self.config.zones.iter().position(|zone| {
+            (zone.start.resolve(length)..zone.end.resolve(length)).contains(&offset)
+        })
+    }
+
+    /// Let the clicks reach the window below the corner once its enter command or a stage ran.
+    fn click_through(&self, origin: &Origin) {
+        if self.config.click_through == ClickThrough::AfterTrigger {
+            self.requests
+                .send(CornerRequest::ClickThrough {
+                    corner: self.config.name.clone(),
+                    origin: origin.clone(),
+                })
+                .ok();
+        }
+    }
+
+    fn publish(&self, kind: EventKind, origin: &Origin) {
+        self.subscribers.publish(&Record {
+            corner: &self.config.name,
+            kind,
+            origin,
+            timestamp: SystemTime::now(),
+        });
     }
 
-    pub fn on_leave_mouse(&self) -> Result<()> {
+    pub fn on_enter_mouse(&self, origin: Origin) -> Result<()> {
+        self.send(CornerEvent::Enter(origin))
+    }
+
+    pub fn on_leave_mouse(&self, origin: Origin) -> Result<()> {
+        self.send(CornerEvent::Leave(origin))
+    }
+
+    pub fn on_button(&self, origin: Origin, button: &'static str) -> Result<()> {
+        self.send(CornerEvent::Button(origin, button))
+    }
+
+    pub fn on_drag(&self, dragging: bool) -> Result<()> {
+        self.send(CornerEvent::Drag(dragging))
+    }
+
+    pub fn on_swipe(&self, origin: Origin) -> Result<()> {
+        self.send(CornerEvent::Swipe(origin))
+    }
+
+    pub fn on_drop(&self, origin: Origin, items: Vec<String>) -> Result<()> {
+        self.send(CornerEvent::Drop(origin, items))
+    }
+
+    pub fn on_scroll(&self, origin: Origin, direction: &'static str) -> Result<()> {
+        self.send(CornerEvent::Scroll(origin, direction))
+    }
+
+    pub fn on_motion(&self, origin: Origin, position: Position, time: Option<u32>) -> Result<()> {
+        self.send(CornerEvent::Motion(origin, position, time))
+    }
+
+    pub fn stop(&self) -> Result<()> {
+        self.send(CornerEvent::Stop)
+    }
+
+    fn send(&self, event: CornerEvent) -> Result<()> {
         self.channel
             .0
             .lock()
             .expect("Cannot get sender")
-            .send(CornerEvent::Leave)?;
+            .send(event)?;
         Ok(())
     }
 
@@ -108,7 +1221,37 @@ impl Corner {
             .unwrap_or(true)
     }
 
-    fn execute_command(&self, command: &[String]) -> Result<()> {
+    /// Ask the Wayland thread for the text of the selection, which is empty when there is none
+    /// or it did not arrive in time.
+    fn read_selection(&self, kind: SelectionKind) -> String {
+        let (reply, text) = channel();
+        if self
+            .requests
+            .send(CornerRequest::ReadSelection { kind, reply })
+            .is_err()
+        {
+            return String::new();
+        }
+        text.recv_timeout(SELECTION_TIMEOUT)
+            .unwrap_or_else(|error| {
+                debug!("Could not read the {}: {:?}", kind.as_str(), error);
+                String::new()
+            })
+    }
+
+    fn execute_command(
+        &self,
+        runner: &mut Runner,
+        command: &CommandConfig,
+        context: &CommandContext,
+    ) {
+        let mut variables = context.variables(&self.config.name);
+        for kind in [SelectionKind::Primary, SelectionKind::Clipboard] {
+            if command.mentions(kind.as_str()) {
+                variables.push((kind.as_str(), self.read_selection(kind)));
+            }
+        }
+        let command = command_line(command, &self.config.env, &variables, &context.arguments);
         if let Some(binary) = command.first() {
             let args = command
                 .iter()
@@ -117,10 +1260,284 @@ impl Corner {
                 .map(|(_, value)| value)
                 .collect::<Vec<_>>();
             info!("executing command: {} {:?}", binary, args);
-            let output = Command::new(binary).args(args).output()?;
-            info!("output received: {:?}", output);
+            let mut command = Command::new(binary);
+            command.args(args).envs(&self.config.env).envs(
+                variables
+                    .iter()
+                    .map(|(key, value)| (command::variable_name(key), value)),
+            );
+            if let Some(working_directory) = &self.config.working_directory {
+                command.current_dir(command::expand(working_directory, &self.config.env));
+            }
+            runner.run(command, context.locks);
         }
+    }
+}
 
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> Vec<(&'static str, String)> {
+        vec![("name", "left".to_owned()), ("dwell_ms", "300".to_owned())]
+    }
+
+    #[test]
+    fn substitute_placeholders() {
+        assert_eq!(substitute("{name}", &variables()), "left");
+        assert_eq!(
+            substitute("corner {name} after {dwell_ms}ms", &variables()),
+            "corner left after 300ms"
+        );
+        assert_eq!(substitute("{name}{name}", &variables()), "leftleft");
+    }
+
+    #[test]
+    fn substitute_keeps_unknown_placeholders() {
+        assert_eq!(substitute("{unknown}", &variables()), "{unknown}");
+        assert_eq!(substitute("{}", &variables()), "{}");
+        assert_eq!(substitute("{{name}}", &variables()), "{left}");
+        assert_eq!(
+            substitute("awk '{print $1}' {name}", &variables()),
+            "awk '{print $1}' left"
+        );
+    }
+
+    #[test]
+    fn substitute_keeps_unbalanced_braces() {
+        assert_eq!(substitute("{name", &variables()), "{name");
+        assert_eq!(substitute("name}", &variables()), "name}");
+        assert_eq!(substitute("${name", &variables()), "${name");
+        assert_eq!(substitute("{ {name}", &variables()), "{ left");
+    }
+
+    #[test]
+    fn shell_commands_reference_variables() {
+        let variables = vec![("selection", "\"; touch injected; \"".to_owned())];
+        let command = command_line(
+            &CommandConfig::Shell("notify-send {selection}".to_owned()),
+            &HashMap::new(),
+            &variables,
+            &[],
+        );
+        assert_eq!(command[1..], ["-c", "notify-send \"$WAYCORNER_SELECTION\""]);
+        let command = command_line(
+            &CommandConfig::Shell("echo {unknown} \"$@\"".to_owned()),
+            &HashMap::new(),
+            &variables,
+            &["a b".to_owned()],
+        );
+        assert_eq!(
+            command[1..],
+            ["-c", "echo {unknown} \"$@\"", "waycorner", "a b"]
+        );
+    }
+
+    #[test]
+    fn argv_commands_substitute_values() {
+        let variables = vec![("selection", "$(touch injected)".to_owned())];
+        let command = command_line(
+            &CommandConfig::Argv(vec!["notify-send".to_owned(), "{selection}".to_owned()]),
+            &HashMap::new(),
+            &variables,
+            &["a b".to_owned()],
+        );
+        assert_eq!(command, ["notify-send", "$(touch injected)", "a b"]);
+        assert!(command_line(
+            &CommandConfig::Shell(" ".to_owned()),
+            &HashMap::new(),
+            &variables,
+            &[]
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn substitute_does_not_substitute_values() {
+        let variables = vec![
+            ("name", "{dwell_ms}".to_owned()),
+            ("dwell_ms", "300".to_owned()),
+        ];
+        assert_eq!(substitute("{name}", &variables), "{dwell_ms}");
+    }
+
+    fn position(x: f64, y: f64) -> Position {
+        Position {
+            x,
+            y,
+            width: 10,
+            height: 10,
+        }
+    }
+
+    fn edge_position(x: f64, y: f64) -> Position {
+        Position {
+            x,
+            y,
+            width: 100,
+            height: 10,
+        }
+    }
+
+    #[test]
+    fn border_sides_of_locations() {
+        for (location, margin, sides) in [
+            (Location::TopLeft, 20, [true, true, false, false]),
+            (Location::BottomRight, 0, [false, false, true, true]),
+            (Location::Top, 20, [false, true, false, false]),
+            (Location::Top, 0, [true, true, true, false]),
+            (Location::Top, -5, [true, true, true, false]),
+            (Location::Right, 1, [false, false, true, false]),
+            (Location::Right, 0, [false, true, true, true]),
+        ] {
+            assert_eq!(
+                border_sides(&location, margin),
+                sides,
+                "{:?} with margin {}",
+                location,
+                margin
+            );
+        }
+    }
+
+    #[test]
+    fn depth_ignores_border_sides() {
+        let top_left = border_sides(&Location::TopLeft, 20);
+        assert_eq!(position(1.0, 2.0).depth(top_left), 8.0);
+        assert_eq!(position(9.0, 2.0).depth(top_left), 1.0);
+        // The ends of an edge can only be crossed when it has a margin.
+        assert_eq!(
+            edge_position(3.0, 2.0).depth(border_sides(&Location::Top, 20)),
+            3.0
+        );
+        assert_eq!(
+            edge_position(3.0, 2.0).depth(border_sides(&Location::Top, 0)),
+            8.0
+        );
+    }
+
+    #[test]
+    fn approach_from_first_position() {
+        let top_left = border_sides(&Location::TopLeft, 20);
+        for (position, sides, approach) in [
+            // Sliding down the left border into the bottom of the corner.
+            (position(0.5, 9.5), top_left, Approach::AlongEdge),
+            (position(9.5, 0.5), top_left, Approach::AlongEdge),
+            // Coming from the inside of the output.
+            (position(5.0, 9.5), top_left, Approach::FromInside),
+            (position(9.5, 5.0), top_left, Approach::FromInside),
+            // Touching the border but already deep into the corner.
+            (position(0.5, 5.0), top_left, Approach::FromInside),
+            (
+                edge_position(0.5, 0.5),
+                border_sides(&Location::Top, 20),
+                Approach::AlongEdge,
+            ),
+            (
+                edge_position(0.5, 0.5),
+                border_sides(&Location::Top, 0),
+                Approach::FromInside,
+            ),
+            (
+                edge_position(50.0, 9.5),
+                border_sides(&Location::Top, 20),
+                Approach::FromInside,
+            ),
+        ] {
+            assert_eq!(position.approach(sides), approach, "{:?}", position);
+        }
+    }
+
+    #[test]
+    fn velocity_from_motion_times() {
+        let origin = Origin {
+            location: Location::TopLeft,
+            output: String::new(),
+        };
+        let sides = border_sides(&Location::TopLeft, 20);
+        let mut arrival = Arrival::new(origin.clone());
+        assert_eq!(
+            arrival.motion(position(5.0, 9.5), Some(100), sides),
+            Some(Approach::FromInside)
+        );
+        assert!(!arrival.sampled());
+        assert_eq!(arrival.motion(position(5.0, 3.5), Some(110), sides), None);
+        assert_eq!(arrival.motion(position(5.0, 1.5), Some(150), sides), None);
+        assert!(arrival.sampled());
+        assert_eq!(arrival.velocity(), 160.0);
+
+        // The pointer stopped right away, so only how far it got since entering is known.
+        let mut arrival = Arrival::new(origin);
+        arrival.received_at -= Duration::from_millis(100);
+        arrival.motion(position(5.0, 9.0), Some(100), sides);
+        arrival.motion(position(5.0, 8.0), None, sides);
+        let velocity = arrival.velocity();
+        assert!(velocity > 0.0 && velocity <= 20.0, "{}", velocity);
+    }
+
+    #[test]
+    fn exit_direction_from_last_positions() {
+        let top_left = border_sides(&Location::TopLeft, 20);
+        let top = border_sides(&Location::Top, 20);
+        for (last, previous, sides, direction) in [
+            // Heading down, out of the bottom of the corner.
+            (
+                position(5.0, 5.0),
+                Some(position(5.0, 4.0)),
+                top_left,
+                ExitDirection::Inward,
+            ),
+            // Sliding down the left border.
+            (
+                position(0.5, 9.5),
+                Some(position(0.5, 8.5)),
+                top_left,
+                ExitDirection::AlongEdge,
+            ),
+            // Heading up, off the output.
+            (
+                position(5.0, 0.5),
+                Some(position(5.0, 1.5)),
+                top_left,
+                ExitDirection::Outward,
+            ),
+            // Heading left at the top, reaching the left side first.
+            (
+                position(1.0, 5.0),
+                Some(position(3.0, 4.0)),
+                top_left,
+                ExitDirection::Outward,
+            ),
+            // Without movement the closest side is taken.
+            (position(9.5, 5.0), None, top_left, ExitDirection::Inward),
+            (
+                position(9.5, 5.0),
+                Some(position(9.5, 5.0)),
+                top_left,
+                ExitDirection::Inward,
+            ),
+            (position(0.5, 5.0), None, top_left, ExitDirection::Outward),
+            // The ends of an edge with a margin are crossed along the border.
+            (
+                edge_position(0.5, 0.5),
+                Some(edge_position(1.5, 0.5)),
+                top,
+                ExitDirection::AlongEdge,
+            ),
+            (
+                edge_position(0.5, 0.5),
+                Some(edge_position(1.5, 0.5)),
+                border_sides(&Location::Top, 0),
+                ExitDirection::Outward,
+            ),
+        ] {
+            assert_eq!(
+                last.exit_direction(previous.as_ref(), sides),
+                direction,
+                "{:?} from {:?}",
+                last,
+                previous
+            );
+        }
     }
 }
@@ -0,0 +1,174 @@
+use std::{
+    fmt::Write as _,
+    io::{ErrorKind, Write},
+    os::unix::net::UnixStream,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use tracing::{debug, warn};
+
+use crate::corner::Origin;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    /// The pointer entered the corner.
+    Enter,
+    /// The pointer left the corner.
+    Leave,
+    /// The pointer stayed in the corner long enough to run the enter command.
+    Triggered,
+    /// The pointer left the corner before the enter command was run.
+    Cancelled,
+}
+
+impl EventKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Enter => "enter",
+            EventKind::Leave => "leave",
+            EventKind::Triggered => "triggered",
+            EventKind::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A single corner event as sent to subscribers.
+#[derive(Debug)]
+pub struct Record<'a> {
+    pub corner: &'a str,
+    pub kind: EventKind,
+    pub origin: &'a Origin,
+    pub timestamp: SystemTime,
+}
+
+impl Record<'_> {
+    fn timestamp_ms(&self) -> u128 {
+        self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|value| value.as_millis())
+            .unwrap_or_default()
+    }
+
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"corner\":{},\"kind\":{},\"location\":{},\"output\":{},\"timestamp_ms\":{}}}",
+            json_string(self.corner),
+            json_string(self.kind.as_str()),
+            json_string(&self.origin.location.to_string()),
+            json_string(&self.origin.output),
+            self.timestamp_ms(),
+        )
+    }
+
+    pub fn to_text(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.timestamp_ms(),
+            self.corner,
+            self.kind.as_str(),
+            self.origin.location,
+            self.origin.output,
+        )
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for character in value.chars() {
+        match character {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            character if character.is_control() => {
+                write!(result, "\\u{:04x}", character as u32).unwrap();
+            }
+            character => result.push(character),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Connections that subscribed to the corner events, one record per line.
+#[derive(Debug, Default)]
+pub struct Subscribers {
+    streams: Mutex<Vec<(UnixStream, bool)>>,
+}
+
+impl Subscribers {
+    pub fn add(&self, stream: UnixStream, json: bool) {
+        // Never let a slow subscriber block the corner, it is dropped instead.
+        if let Err(error) = stream.set_nonblocking(true) {
+            warn!("Could not add subscriber: {:?}", error);
+            return;
+        }
+        self.streams
+            .lock()
+            .expect("cannot get subscribers")
+            .push((stream, json));
+    }
+
+    pub fn publish(&self, record: &Record) {
+        let mut streams = self.streams.lock().expect("cannot get subscribers");
+        if streams.is_empty() {
+            return;
+        }
+        debug!("Publishing event: {:?}", record);
+        let json = format!("{}\n", record.to_json());
+        let text = format!("{}\n", record.to_text());
+        streams.retain_mut(|(stream, is_json)| {
+            let line = if *is_json { &json } else { &text };
+            match stream.write_all(line.as_bytes()) {
+                Ok(()) => true,
+                Err(error) => {
+                    if error.kind() == ErrorKind::WouldBlock {
+                        warn!("Dropping subscriber that does not keep up with the events");
+                    } else {
+                        debug!("Dropping subscriber: {:?}", error);
+                    }
+                    false
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::config::Location;
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string(""), r#""""#);
+        assert_eq!(json_string("DP-1 \"Dell\""), r#""DP-1 \"Dell\"""#);
+        assert_eq!(json_string(r"C:\corner"), r#""C:\\corner""#);
+        assert_eq!(json_string("a\nb\r\tc"), r#""a\nb\r\tc""#);
+        assert_eq!(json_string("\u{0}\u{1b}\u{7f}"), r#""\u0000\u001b\u007f""#);
+        assert_eq!(json_string("écran ✓"), "\"écran ✓\"");
+    }
+
+    #[test]
+    fn record_to_json() {
+        let origin = Origin {
+            location: Location::TopLeft,
+            output: "Dell \"U2720Q\"\\1\n".to_owned(),
+        };
+        let record = Record {
+            corner: "my \"corner\"",
+            kind: EventKind::Enter,
+            origin: &origin,
+            timestamp: UNIX_EPOCH + Duration::from_millis(1500),
+        };
+        assert_eq!(
+            record.to_json(),
+            r#"{"corner":"my \"corner\"","kind":"enter","location":"top_left","output":"Dell \"U2720Q\"\\1\n","timestamp_ms":1500}"#
+        );
+    }
+}
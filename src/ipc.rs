@@ -0,0 +1,263 @@
+use std::{
+    env, fmt, fs,
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    os::unix::{
+        io::{AsRawFd, RawFd},
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use calloop::channel::Sender;
+use tracing::{debug, info, warn};
+
+/// Requests that can be sent over the control socket, one per connection.
+#[derive(Debug, PartialEq)]
+pub enum Request {
+    List,
+    /// Enable the corner with the given name, or all corners.
+    Enable(Option<String>),
+    /// Disable the corner with the given name, or all corners.
+    Disable(Option<String>),
+    /// Toggle the corner with the given name, or all corners.
+    Toggle(Option<String>),
+    /// Keep the connection open and stream the corner events, as JSON or tab separated text.
+    Subscribe {
+        json: bool,
+    },
+}
+
+impl FromStr for Request {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Request> {
+        // The name is the rest of the line, as the names of corners may contain spaces.
+        let (command, name) = value
+            .trim()
+            .split_once(char::is_whitespace)
+            .map(|(command, name)| (command, Some(name.trim().to_owned())))
+            .unwrap_or((value.trim(), None));
+        if command.is_empty() {
+            bail!("empty request")
+        }
+        Ok(match (command, name) {
+            ("list", None) => Request::List,
+            ("enable", name) => Request::Enable(name),
+            ("disable", name) => Request::Disable(name),
+            ("toggle", name) => Request::Toggle(name),
+            ("subscribe", None) => Request::Subscribe { json: false },
+            ("subscribe", Some(format)) if format == "json" => Request::Subscribe { json: true },
+            _ => bail!("unknown request `{}`", value),
+        })
+    }
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (command, name) = match self {
+            Request::List => ("list", None),
+            Request::Enable(name) => ("enable", name.as_ref()),
+            Request::Disable(name) => ("disable", name.as_ref()),
+            Request::Toggle(name) => ("toggle", name.as_ref()),
+            Request::Subscribe { json: false } => ("subscribe", None),
+            Request::Subscribe { json: true } => return f.write_str("subscribe json"),
+        };
+        match name {
+            Some(name) => write!(f, "{} {}", command, name),
+            None => f.write_str(command),
+        }
+    }
+}
+
+/// Path of the control socket, unique per Wayland display.
+pub fn socket_path() -> Result<PathBuf> {
+    let runtime_dir = env::var_os("XDG_RUNTIME_DIR")
+        .context("could not find the $XDG_RUNTIME_DIR env var to use for the control socket")?;
+    let display = env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".to_owned());
+    Ok(PathBuf::from(runtime_dir).join(format!("waycorner-{}.sock", display)))
+}
+
+fn connect(request: &Request) -> Result<UnixStream> {
+    let path = socket_path()?;
+    let mut stream = UnixStream::connect(&path).with_context(|| {
+        format!(
+            "could not connect to {}, is waycorner running?",
+            path.display()
+        )
+    })?;
+    writeln!(stream, "{}", request)?;
+    Ok(stream)
+}
+
+/// Send a request to the running waycorner instance and return its response.
+pub fn send(request: &Request) -> Result<String> {
+    let mut stream = connect(request)?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    if let Some(error) = response.strip_prefix("error: ") {
+        bail!("{}", error.trim_end())
+    }
+    Ok(response)
+}
+
+/// Subscribe to the corner events of the running waycorner instance and copy them to stdout.
+pub fn subscribe(json: bool) -> Result<()> {
+    let stream = connect(&Request::Subscribe { json })?;
+    let mut stdout = io::stdout().lock();
+    for line in BufReader::new(stream).lines() {
+        writeln!(stdout, "{}", line?)?;
+        stdout.flush()?;
+    }
+    Ok(())
+}
+
+/// The listening side of the control socket.
+pub struct ControlSocket {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl ControlSocket {
+    pub fn bind() -> Result<ControlSocket> {
+        let path = socket_path()?;
+        if UnixStream::connect(&path).is_ok() {
+            bail!(
+                "another waycorner instance is listening on {}",
+                path.display()
+            )
+        }
+        match fs::remove_file(&path) {
+            Err(error) if error.kind() != ErrorKind::NotFound => {
+                return Err(error)
+                    .with_context(|| format!("could not remove stale socket {}", path.display()))
+            }
+            _ => (),
+        }
+
+        let listener = UnixListener::bind(&path)
+            .with_context(|| format!("could not bind to {}", path.display()))?;
+        listener.set_nonblocking(true)?;
+        info!("Listening on {}", path.display());
+        Ok(ControlSocket { listener, path })
+    }
+
+    /// Accept all pending connections and send the request of each of them. The requests are
+    /// read on another thread, so a slow client does not hold up the caller.
+    pub fn accept(&self, requests: &Sender<(UnixStream, Request)>) {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let requests = requests.clone();
+                    thread::spawn(move || match ControlSocket::read_request(&stream) {
+                        Ok(request) => {
+                            debug!("Received request: {:?}", request);
+                            requests.send((stream, request)).ok();
+                        }
+                        Err(error) => respond(stream, Err(error)),
+                    });
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    warn!("Could not accept connection: {:?}", error);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn read_request(stream: &UnixStream) -> Result<Request> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line)?;
+        line.parse()
+    }
+}
+
+impl AsRawFd for ControlSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.listener.as_raw_fd()
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+/// Write the response to a request and close the connection.
+pub fn respond(mut stream: UnixStream, response: Result<String>) {
+    let response = response.unwrap_or_else(|error| format!("error: {:#}\n", error));
+    if let Err(error) = stream.write_all(response.as_bytes()) {
+        warn!("Could not send response: {:?}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_requests() {
+        assert_eq!("list".parse::<Request>().unwrap(), Request::List);
+        assert_eq!("enable".parse::<Request>().unwrap(), Request::Enable(None));
+        assert_eq!(
+            " disable  left\n".parse::<Request>().unwrap(),
+            Request::Disable(Some("left".to_owned()))
+        );
+        assert_eq!(
+            "toggle left".parse::<Request>().unwrap(),
+            Request::Toggle(Some("left".to_owned()))
+        );
+        assert_eq!(
+            "enable  my corner \n".parse::<Request>().unwrap(),
+            Request::Enable(Some("my corner".to_owned()))
+        );
+        assert_eq!(
+            "subscribe".parse::<Request>().unwrap(),
+            Request::Subscribe { json: false }
+        );
+        assert_eq!(
+            "subscribe json".parse::<Request>().unwrap(),
+            Request::Subscribe { json: true }
+        );
+    }
+
+    #[test]
+    fn parse_invalid_requests() {
+        for value in [
+            "",
+            "\n",
+            "unknown",
+            "list left",
+            "subscribe xml",
+            "Enable left",
+        ] {
+            assert!(
+                value.parse::<Request>().is_err(),
+                "{:?} should be invalid",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        for request in [
+            Request::List,
+            Request::Enable(None),
+            Request::Disable(Some("left".to_owned())),
+            Request::Toggle(Some("top-edge".to_owned())),
+            Request::Enable(Some("my corner".to_owned())),
+            Request::Subscribe { json: false },
+            Request::Subscribe { json: true },
+        ] {
+            assert_eq!(request.to_string().parse::<Request>().unwrap(), request);
+        }
+    }
+}
@@ -1,10 +1,19 @@
+mod chord;
+mod command;
 mod config;
 mod corner;
+mod events;
+mod ipc;
+mod selection;
+mod tablet;
+mod toplevel;
+mod watcher;
 mod wayland;
 
 use anyhow::Result;
-use clap::Parser;
-use config::get_configs;
+use clap::{Parser, Subcommand};
+use config::{get_configs, resolve_path};
+use ipc::Request;
 use std::path::PathBuf;
 use wayland::Wayland;
 
@@ -19,6 +28,35 @@ struct Opts {
     /// Preview the corners on your screen(s).
     #[clap(short, long)]
     preview: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Control the running waycorner instance.
+    Ctl {
+        #[clap(subcommand)]
+        action: CtlAction,
+    },
+    /// Stream the corner events of the running waycorner instance, one per line.
+    Events {
+        /// Print the events as JSON instead of tab separated text.
+        #[clap(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum CtlAction {
+    /// List the corners with their state, locations and matched outputs.
+    List,
+    /// Enable a corner, or all corners if no name is given.
+    Enable { name: Option<String> },
+    /// Disable a corner, or all corners if no name is given.
+    Disable { name: Option<String> },
+    /// Toggle a corner, or all corners if no name is given.
+    Toggle { name: Option<String> },
 }
 
 fn main() -> Result<()> {
@@ -26,6 +64,23 @@ fn main() -> Result<()> {
 
     let opts = Opts::parse();
 
-    let mut frontend = Wayland::new(get_configs(opts.config)?, opts.preview);
+    match opts.command {
+        Some(Command::Ctl { action }) => {
+            let request = match action {
+                CtlAction::List => Request::List,
+                CtlAction::Enable { name } => Request::Enable(name),
+                CtlAction::Disable { name } => Request::Disable(name),
+                CtlAction::Toggle { name } => Request::Toggle(name),
+            };
+            print!("{}", ipc::send(&request)?);
+            return Ok(());
+        }
+        Some(Command::Events { json }) => return ipc::subscribe(json),
+        None => (),
+    }
+
+    let config_path = resolve_path(opts.config)?;
+    let config = get_configs(&config_path)?;
+    let mut frontend = Wayland::new(config_path, config, opts.preview);
     frontend.run()
 }
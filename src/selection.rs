@@ -0,0 +1,161 @@
+use std::{cell::RefCell, fs::File, os::unix::io::FromRawFd, rc::Rc};
+
+use anyhow::Result;
+use nix::{fcntl::OFlag, unistd};
+use tracing::debug;
+use wayland_client::{protocol::wl_seat::WlSeat, Main};
+use wayland_protocols::wlr::unstable::data_control::v1::client::{
+    zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+    zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+    zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+};
+
+/// Text types the selections are received as, most preferred first.
+const TEXT_MIME_TYPES: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "TEXT",
+    "STRING",
+];
+
+/// Which selection to read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionKind {
+    /// The text that is selected, pasted with the middle button.
+    Primary,
+    Clipboard,
+}
+
+impl SelectionKind {
+    /// Name of the variable the selection is passed to commands as.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SelectionKind::Primary => "selection",
+            SelectionKind::Clipboard => "clipboard",
+        }
+    }
+}
+
+/// Data offered by another client, with the MIME types it is offered as.
+struct Offer {
+    offer: ZwlrDataControlOfferV1,
+    mime_types: Rc<RefCell<Vec<String>>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Offers that were introduced but not made a selection yet.
+    offers: Vec<Offer>,
+    primary: Option<Offer>,
+    clipboard: Option<Offer>,
+}
+
+/// Keeps track of the selections of a seat with the wlr data control protocol, which unlike the
+/// regular data device does not need keyboard focus to see them.
+pub struct Selections {
+    device: Main<ZwlrDataControlDeviceV1>,
+    state: Rc<RefCell<State>>,
+}
+
+impl Selections {
+    pub fn new(manager: &ZwlrDataControlManagerV1, seat: &WlSeat) -> Selections {
+        let state = Rc::new(RefCell::new(State::default()));
+        let device = manager.get_data_device(seat);
+        let device_state = Rc::clone(&state);
+        device.quick_assign(move |_, event, _| {
+            let mut state = device_state.borrow_mut();
+            match event {
+                zwlr_data_control_device_v1::Event::DataOffer { id } => {
+                    let mime_types = Rc::new(RefCell::new(vec![]));
+                    let offer_mime_types = Rc::clone(&mime_types);
+                    id.quick_assign(move |_, event, _| {
+                        if let zwlr_data_control_offer_v1::Event::Offer { mime_type } = event {
+                            offer_mime_types.borrow_mut().push(mime_type);
+                        }
+                    });
+                    state.offers.push(Offer {
+                        offer: id.detach(),
+                        mime_types,
+                    });
+                }
+                zwlr_data_control_device_v1::Event::Selection { id } => {
+                    let offer = state.take_offer(id);
+                    state.set(SelectionKind::Clipboard, offer);
+                }
+                zwlr_data_control_device_v1::Event::PrimarySelection { id } => {
+                    let offer = state.take_offer(id);
+                    state.set(SelectionKind::Primary, offer);
+                }
+                zwlr_data_control_device_v1::Event::Finished => {
+                    debug!("Data control device finished");
+                    state.set(SelectionKind::Primary, None);
+                    state.set(SelectionKind::Clipboard, None);
+                }
+                _ => (),
+            }
+        });
+        Selections { device, state }
+    }
+
+    /// Request the text of the selection, which can be read from the returned pipe once the
+    /// request is flushed. Returns `None` when the selection is empty or not text.
+    pub fn receive(&self, kind: SelectionKind) -> Result<Option<File>> {
+        let state = self.state.borrow();
+        let offer = match kind {
+            SelectionKind::Primary => state.primary.as_ref(),
+            SelectionKind::Clipboard => state.clipboard.as_ref(),
+        };
+        let Some(offer) = offer else {
+            return Ok(None);
+        };
+        let mime_type = {
+            let mime_types = offer.mime_types.borrow();
+            TEXT_MIME_TYPES
+                .into_iter()
+                .find(|mime_type| mime_types.iter().any(|value| value == mime_type))
+        };
+        let Some(mime_type) = mime_type else {
+            debug!("The {} is not offered as text", kind.as_str());
+            return Ok(None);
+        };
+
+        let (read, write) = unistd::pipe2(OFlag::O_CLOEXEC)?;
+        offer.offer.receive(mime_type.to_owned(), write);
+        unistd::close(write)?;
+        // SAFETY: The read end of the pipe was just created and is not owned by anything else.
+        Ok(Some(unsafe { File::from_raw_fd(read) }))
+    }
+}
+
+impl State {
+    /// Take the offer out of the introduced ones.
+    fn take_offer(&mut self, id: Option<ZwlrDataControlOfferV1>) -> Option<Offer> {
+        let id = id?;
+        let index = self.offers.iter().position(|offer| offer.offer == id)?;
+        Some(self.offers.remove(index))
+    }
+
+    fn set(&mut self, kind: SelectionKind, offer: Option<Offer>) {
+        let slot = match kind {
+            SelectionKind::Primary => &mut self.primary,
+            SelectionKind::Clipboard => &mut self.clipboard,
+        };
+        if let Some(previous) = std::mem::replace(slot, offer) {
+            previous.offer.destroy();
+        }
+    }
+}
+
+impl Drop for Selections {
+    fn drop(&mut self) {
+        let mut state = self.state.borrow_mut();
+        state.set(SelectionKind::Primary, None);
+        state.set(SelectionKind::Clipboard, None);
+        state
+            .offers
+            .drain(..)
+            .for_each(|offer| offer.offer.destroy());
+        self.device.destroy();
+    }
+}
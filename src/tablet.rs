@@ -0,0 +1,57 @@
+use std::sync::mpsc::Sender;
+
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_protocols::unstable::tablet::v2::client::{
+    zwp_tablet_manager_v2::ZwpTabletManagerV2,
+    zwp_tablet_pad_group_v2, zwp_tablet_pad_v2, zwp_tablet_seat_v2,
+    zwp_tablet_tool_v2::{self, ZwpTabletToolV2},
+    zwp_tablet_v2,
+};
+
+/// An event of a tablet tool like a pen, with the tool it happened on.
+pub type ToolEvent = (ZwpTabletToolV2, zwp_tablet_tool_v2::Event);
+
+/// Listen for the tools of the tablets of the seat and send their events. The tablets and pads
+/// themselves are ignored, but still need to be handled to be destroyed when removed.
+pub fn listen(manager: &ZwpTabletManagerV2, seat: &WlSeat, tx: Sender<ToolEvent>) {
+    manager
+        .get_tablet_seat(seat)
+        .quick_assign(move |_, event, _| match event {
+            zwp_tablet_seat_v2::Event::TabletAdded { id } => {
+                id.quick_assign(|tablet, event, _| {
+                    if let zwp_tablet_v2::Event::Removed = event {
+                        tablet.destroy();
+                    }
+                });
+            }
+            zwp_tablet_seat_v2::Event::ToolAdded { id } => {
+                let tx = tx.clone();
+                id.quick_assign(move |tool, event, _| {
+                    let removed = matches!(event, zwp_tablet_tool_v2::Event::Removed);
+                    tx.send((tool.detach(), event))
+                        .expect("could not send event on channel");
+                    if removed {
+                        tool.destroy();
+                    }
+                });
+            }
+            zwp_tablet_seat_v2::Event::PadAdded { id } => {
+                id.quick_assign(|pad, event, _| match event {
+                    zwp_tablet_pad_v2::Event::Group { pad_group } => {
+                        pad_group.quick_assign(|_, event, _| match event {
+                            zwp_tablet_pad_group_v2::Event::Ring { ring } => {
+                                ring.quick_assign(|_, _, _| {});
+                            }
+                            zwp_tablet_pad_group_v2::Event::Strip { strip } => {
+                                strip.quick_assign(|_, _, _| {});
+                            }
+                            _ => (),
+                        });
+                    }
+                    zwp_tablet_pad_v2::Event::Removed => pad.destroy(),
+                    _ => (),
+                });
+            }
+            _ => (),
+        });
+}
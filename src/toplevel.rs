@@ -0,0 +1,153 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use smithay_client_toolkit::environment::GlobalHandler;
+use tracing::info;
+use wayland_client::{
+    protocol::{wl_output::WlOutput, wl_registry::WlRegistry},
+    Attached, DispatchData, Interface,
+};
+use wayland_protocols::wlr::unstable::foreign_toplevel::v1::client::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+
+/// A window of any client.
+struct Toplevel {
+    handle: ZwlrForeignToplevelHandleV1,
+    /// Outputs the toplevel is visible on.
+    outputs: Vec<WlOutput>,
+    minimized: bool,
+}
+
+/// Keeps track of the windows of all clients with the wlr foreign toplevel management protocol,
+/// to minimise and restore them for the built-in actions.
+pub struct Toplevels {
+    toplevels: Rc<RefCell<Vec<Toplevel>>>,
+    /// The toplevels each corner minimised, keyed by the name of the corner.
+    minimized_by: HashMap<String, Vec<ZwlrForeignToplevelHandleV1>>,
+}
+
+/// Binds the foreign toplevel manager, which announces the toplevels as soon as it is bound so
+/// they have to be handled from the start.
+#[derive(Default)]
+pub struct ToplevelHandler {
+    manager: Option<Attached<ZwlrForeignToplevelManagerV1>>,
+    toplevels: Rc<RefCell<Vec<Toplevel>>>,
+}
+
+impl ToplevelHandler {
+    /// The toplevels, if the compositor lets us manage them.
+    pub fn toplevels(&self) -> Option<Toplevels> {
+        self.manager.as_ref().map(|_| Toplevels {
+            toplevels: Rc::clone(&self.toplevels),
+            minimized_by: HashMap::new(),
+        })
+    }
+}
+
+impl GlobalHandler<ZwlrForeignToplevelManagerV1> for ToplevelHandler {
+    fn created(&mut self, registry: Attached<WlRegistry>, id: u32, version: u32, _: DispatchData) {
+        let manager = registry.bind::<ZwlrForeignToplevelManagerV1>(
+            ZwlrForeignToplevelManagerV1::VERSION.min(version),
+            id,
+        );
+        let toplevels = Rc::clone(&self.toplevels);
+        manager.quick_assign(move |_, event, _| {
+            if let zwlr_foreign_toplevel_manager_v1::Event::Toplevel { toplevel } = event {
+                let handle_toplevels = Rc::clone(&toplevels);
+                toplevel.quick_assign(move |handle, event, _| {
+                    Toplevels::toplevel_handler(&handle_toplevels, &handle, event);
+                });
+                toplevels.borrow_mut().push(Toplevel {
+                    handle: toplevel.detach(),
+                    outputs: vec![],
+                    minimized: false,
+                });
+            }
+        });
+        self.manager = Some((*manager).clone());
+    }
+
+    fn get(&self) -> Option<Attached<ZwlrForeignToplevelManagerV1>> {
+        self.manager.clone()
+    }
+}
+
+impl Toplevels {
+    fn toplevel_handler(
+        toplevels: &RefCell<Vec<Toplevel>>,
+        handle: &ZwlrForeignToplevelHandleV1,
+        event: zwlr_foreign_toplevel_handle_v1::Event,
+    ) {
+        let mut toplevels = toplevels.borrow_mut();
+        if let zwlr_foreign_toplevel_handle_v1::Event::Closed = event {
+            toplevels.retain(|toplevel| toplevel.handle != *handle);
+            handle.destroy();
+            return;
+        }
+        let Some(toplevel) = toplevels
+            .iter_mut()
+            .find(|toplevel| toplevel.handle == *handle)
+        else {
+            return;
+        };
+        match event {
+            zwlr_foreign_toplevel_handle_v1::Event::OutputEnter { output } => {
+                toplevel.outputs.push(output);
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::OutputLeave { output } => {
+                toplevel.outputs.retain(|value| *value != output);
+            }
+            zwlr_foreign_toplevel_handle_v1::Event::State { state } => {
+                let minimized = zwlr_foreign_toplevel_handle_v1::State::Minimized.to_raw();
+                toplevel.minimized = state
+                    .chunks_exact(4)
+                    .any(|value| value.try_into().map(u32::from_ne_bytes).ok() == Some(minimized));
+            }
+            _ => (),
+        }
+    }
+
+    /// Whether the corner minimised toplevels that were not restored yet.
+    pub fn has_minimized(&self, corner: &str) -> bool {
+        self.minimized_by
+            .get(corner)
+            .is_some_and(|handles| !handles.is_empty())
+    }
+
+    /// Minimise all toplevels on the output and remember them for the corner.
+    pub fn show_desktop(&mut self, corner: &str, output: &WlOutput) {
+        let handles = self
+            .toplevels
+            .borrow()
+            .iter()
+            .filter(|toplevel| !toplevel.minimized && toplevel.outputs.contains(output))
+            .map(|toplevel| toplevel.handle.clone())
+            .collect::<Vec<_>>();
+        info!("Minimising {} windows", handles.len());
+        handles.iter().for_each(|handle| handle.set_minimized());
+        self.minimized_by
+            .entry(corner.to_owned())
+            .or_default()
+            .extend(handles);
+    }
+
+    /// Restore the toplevels the corner minimised, unless they were closed or restored since.
+    pub fn restore(&mut self, corner: &str) {
+        let Some(handles) = self.minimized_by.remove(corner) else {
+            return;
+        };
+        let toplevels = self.toplevels.borrow();
+        let mut restored = 0;
+        for handle in &handles {
+            if toplevels
+                .iter()
+                .any(|toplevel| toplevel.handle == *handle && toplevel.minimized)
+            {
+                handle.unset_minimized();
+                restored += 1;
+            }
+        }
+        info!("Restoring {} of {} windows", restored, handles.len());
+    }
+}
@@ -0,0 +1,62 @@
+use std::{
+    ffi::OsString,
+    os::unix::io::{AsRawFd, RawFd},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+use tracing::debug;
+
+/// Watches the directory of the config file for changes to the config file.
+///
+/// The directory is watched instead of the file itself as most editors replace
+/// the file on save, which would silently drop a watch on the file.
+pub struct ConfigWatcher {
+    inotify: Inotify,
+    file_name: OsString,
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> Result<ConfigWatcher> {
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("invalid config path specified: {}", path.display()))?
+            .to_owned();
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .context("could not initialize inotify")?;
+        inotify
+            .add_watch(
+                directory,
+                AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_MOVED_TO,
+            )
+            .with_context(|| format!("could not watch {}", directory.display()))?;
+
+        Ok(ConfigWatcher { inotify, file_name })
+    }
+
+    /// Consume the pending events and return whether any of them concern the config file.
+    pub fn changed(&self) -> bool {
+        let mut changed = false;
+        while let Ok(events) = self.inotify.read_events() {
+            for event in events {
+                debug!("Received inotify event: {:?}", event);
+                if event.name.as_ref() == Some(&self.file_name) {
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+impl AsRawFd for ConfigWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inotify.as_raw_fd()
+    }
+}
@@ -1,70 +1,593 @@
 use crate::{
-    config::{self, CornerConfig, Location},
-    corner::Corner,
+    chord::Chords,
+    config::{self, get_configs, Action, ClickThrough, Config, CornerConfig, Location, Restore},
+    corner::{Corner, CornerRequest, Origin, Position},
+    events::Subscribers,
+    ipc::{self, ControlSocket, Request},
+    selection::Selections,
+    tablet::{self, ToolEvent},
+    toplevel::{ToplevelHandler, Toplevels},
+    watcher::ConfigWatcher,
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 
-use crossbeam_utils::thread;
-use smithay_client_toolkit::shm::Format;
+use calloop::{
+    channel::{self, Channel},
+    generic::Generic,
+    signals::{Signal, Signals},
+    EventLoop, Interest, Mode, PostAction,
+};
+
+use smithay_client_toolkit::shm::{DoubleMemPool, Format};
 use smithay_client_toolkit::{
-    data_device::DataDeviceHandler,
+    data_device::{DataDeviceHandler, DataOffer, DndAction, DndEvent},
     default_environment,
     environment::{Environment, SimpleGlobal},
-    output::{with_output_info, OutputInfo, XdgOutputHandler},
+    output::{add_output_listener, with_output_info, OutputInfo, OutputListener, XdgOutputHandler},
     primary_selection::PrimarySelectionHandler,
-    seat,
+    seat, WaylandSource,
 };
 use std::{
+    cell::{Cell, RefCell},
     convert::TryInto,
-    io::{BufWriter, Seek, SeekFrom, Write},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    os::unix::net::UnixStream,
+    path::PathBuf,
+    rc::Rc,
     sync::{
         mpsc::{self, Receiver, Sender},
-        Arc, Mutex,
+        Arc,
     },
+    thread,
 };
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 
 use wayland_client::{
-    protocol::{wl_output::WlOutput, wl_pointer, wl_surface::WlSurface},
+    protocol::{
+        wl_compositor::WlCompositor, wl_output::WlOutput, wl_pointer, wl_seat::WlSeat,
+        wl_surface::WlSurface, wl_touch,
+    },
     Attached, Display, Main, Proxy,
 };
 use wayland_protocols::{
-    unstable::xdg_output::v1::client::zxdg_output_manager_v1::ZxdgOutputManagerV1,
-    wlr::unstable::layer_shell::v1::client::{
-        zwlr_layer_shell_v1,
-        zwlr_layer_surface_v1::{self, Anchor, ZwlrLayerSurfaceV1},
+    unstable::{
+        tablet::v2::client::{
+            zwp_tablet_manager_v2::ZwpTabletManagerV2,
+            zwp_tablet_tool_v2::{self, ZwpTabletToolV2},
+        },
+        xdg_output::v1::client::zxdg_output_manager_v1::ZxdgOutputManagerV1,
+    },
+    wlr::unstable::{
+        data_control::v1::client::zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+        foreign_toplevel::v1::client::zwlr_foreign_toplevel_manager_v1::ZwlrForeignToplevelManagerV1,
+        layer_shell::v1::client::{
+            zwlr_layer_shell_v1,
+            zwlr_layer_surface_v1::{self, Anchor, ZwlrLayerSurfaceV1},
+        },
     },
 };
 default_environment!(Waycorner,  fields = [
     layer_shell: SimpleGlobal<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+    data_control: SimpleGlobal<ZwlrDataControlManagerV1>,
+    tablet_manager: SimpleGlobal<ZwpTabletManagerV2>,
+    toplevel_manager: ToplevelHandler,
     sctk_xdg_out: XdgOutputHandler,
 ],
 singles = [
     zwlr_layer_shell_v1::ZwlrLayerShellV1 => layer_shell,
+    ZwlrDataControlManagerV1 => data_control,
+    ZwpTabletManagerV2 => tablet_manager,
+    ZwlrForeignToplevelManagerV1 => toplevel_manager,
     ZxdgOutputManagerV1 => sctk_xdg_out,
 ],);
 
 struct GlobalState {
     close_requested: bool,
+    reload_requested: bool,
+    /// Outputs that were added, changed or removed since the last dispatch.
+    output_updates: Vec<(WlOutput, OutputInfo)>,
+    /// Requests received on the control socket since the last dispatch.
+    requests: Vec<(UnixStream, Request)>,
+    /// Requests of the corners since the last dispatch.
+    corner_requests: Vec<CornerRequest>,
+    /// Drag-and-drop events since the last dispatch, with the seat they happened on.
+    drop_events: Vec<(WlSeat, DropEvent)>,
+}
+
+/// A drag-and-drop event, the offer itself stays with the data device of the seat.
+enum DropEvent {
+    /// A drag entered the surface, offering the MIME types.
+    Enter {
+        serial: u32,
+        surface: WlSurface,
+        mime_types: Vec<String>,
+    },
+    Leave,
+    Drop,
+    /// The data dropped onto the target was read.
+    Received {
+        target: DropTarget,
+        data: Vec<u8>,
+    },
+}
+
+/// Text types the dropped data is received as, most preferred first.
+const DROP_MIME_TYPES: [&str; 4] = [
+    "text/uri-list",
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+];
+
+/// The corner surface a drag hovers over, if it can be dropped onto it.
+struct DropTarget {
+    seat: WlSeat,
+    /// Serial of the drag entering the surface.
+    serial: u32,
+    surface: WlSurface,
+    /// The type the dropped data is received as.
+    mime_type: String,
+}
+
+/// A pointer event with the index of the seat it happened on.
+type SeatPointerEvent = (usize, wl_pointer::Event);
+
+/// Scroll distance of smooth scrolling that makes up a single scroll step.
+const SCROLL_STEP: f64 = 15.0;
+
+/// State of the pointer of a seat.
+#[derive(Clone, Default)]
+struct PointerState {
+    /// The surface the pointer is on.
+    focus: Option<WlSurface>,
+    /// Version of the pointer, frames are only sent since version 5.
+    version: u32,
+    /// Scroll distance of the vertical and horizontal axis that did not make up a step yet.
+    scroll: [f64; 2],
+    /// Scroll distance of the vertical and horizontal axis received in the current frame.
+    frame_scroll: [f64; 2],
+    /// Discrete scroll steps of the vertical and horizontal axis received in the current frame.
+    frame_discrete: [i32; 2],
+    /// Buttons that are held.
+    pressed: Vec<u32>,
+}
+
+impl PointerState {
+    /// Turn the scroll events of the current frame into scroll steps per axis.
+    fn scroll_steps(&mut self) -> [i32; 2] {
+        let steps = [0, 1].map(|axis| {
+            if self.frame_discrete[axis] != 0 {
+                self.scroll[axis] = 0.0;
+                return self.frame_discrete[axis];
+            }
+            self.scroll[axis] += self.frame_scroll[axis];
+            let steps = (self.scroll[axis] / SCROLL_STEP).trunc() as i32;
+            self.scroll[axis] -= f64::from(steps) * SCROLL_STEP;
+            steps
+        });
+        self.frame_scroll = [0.0; 2];
+        self.frame_discrete = [0; 2];
+        steps
+    }
+}
+
+/// A touch event with the index of the seat it happened on.
+type SeatTouchEvent = (usize, wl_touch::Event);
+
+/// A touch point that started on a corner surface.
+struct TouchPoint {
+    id: i32,
+    surface: WlSurface,
+    /// Where the touch started, in surface coordinates.
+    start: (f64, f64),
+    /// Whether the touch already ran the swipe command.
+    swiped: bool,
+}
+
+/// A layer surface of a corner on a specific output.
+struct CornerSurface {
+    output: WlOutput,
+    location: Location,
+    surface: WlSurface,
+    layer_surface: Main<ZwlrLayerSurfaceV1>,
+    /// Width and height of the surface, as last configured by the compositor.
+    size: Rc<Cell<(u32, u32)>>,
+    compositor: Attached<WlCompositor>,
+    /// Whether only the sliver at the border of the output takes input.
+    click_through: Rc<Cell<bool>>,
+    /// Whether the pointer was on the sliver since the surface became click-through.
+    entered_sliver: Cell<bool>,
+    /// Position of the pointer on the surface, while it is on it.
+    pointer: Cell<Option<(f64, f64)>>,
+    pool: Rc<RefCell<DoubleMemPool>>,
+    /// Color the surface is filled with.
+    color: Rc<Cell<u32>>,
+}
+
+impl CornerSurface {
+    fn destroy(&self) {
+        self.layer_surface.destroy();
+        self.surface.destroy();
+    }
+
+    fn set_click_through(&self, click_through: bool) {
+        self.click_through.set(click_through);
+        // The pointer can already be on the sliver when the corner triggers, leaving it should
+        // then make the whole corner take input again.
+        let on_sliver = self.pointer.get().is_some_and(|(x, y)| {
+            slivers(&self.location, self.size.get()).into_iter().any(
+                |(left, top, width, height)| {
+                    x >= f64::from(left)
+                        && x < f64::from(left + width)
+                        && y >= f64::from(top)
+                        && y < f64::from(top + height)
+                },
+            )
+        });
+        self.entered_sliver.set(click_through && on_sliver);
+        set_input_region(
+            &self.compositor,
+            &self.surface,
+            &self.location,
+            self.size.get(),
+            click_through,
+        );
+        self.surface.commit();
+    }
+
+    /// Fill the surface with another color, like when highlighting it.
+    fn fill(&self, color: u32) {
+        self.color.set(color);
+        if draw(&self.pool, &self.surface, self.size.get(), color) {
+            self.surface.commit();
+        }
+    }
+
+    /// Request the compositor to resize the surface, it is redrawn once configured.
+    fn resize(&self, size: u8) {
+        let (width, height) = layer_size(&self.location, size);
+        self.layer_surface.set_size(width, height);
+        self.surface.commit();
+    }
+}
+
+/// Fill the whole surface with the color. Returns whether a buffer was attached, which is not
+/// the case before the surface is configured or while both buffers are in use.
+fn draw(
+    double_pool: &RefCell<DoubleMemPool>,
+    surface: &WlSurface,
+    (width, height): (u32, u32),
+    color: u32,
+) -> bool {
+    if width == 0 || height == 0 {
+        return false;
+    }
+    let mut double_pool = double_pool.borrow_mut();
+    let Some(pool) = double_pool.pool() else {
+        return false;
+    };
+    let pxcount = width * height;
+    let bytecount = 4 * pxcount;
+
+    pool.resize(bytecount.try_into().unwrap()).unwrap();
+    pool.seek(SeekFrom::Start(0)).unwrap();
+    {
+        let mut writer = BufWriter::new(&mut *pool);
+        for _ in 0..pxcount {
+            writer.write_all(&color.to_ne_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    let buffer = pool.buffer(
+        0,
+        width.try_into().unwrap(),
+        height.try_into().unwrap(),
+        (4 * width).try_into().unwrap(),
+        Format::Argb8888,
+    );
+    surface.attach(Some(&buffer), 0, 0);
+    surface.damage_buffer(0, 0, width.try_into().unwrap(), height.try_into().unwrap());
+    true
+}
+
+/// Let either the whole surface take input, or only a sliver of one pixel at the border of the
+/// output so the clicks on the rest of the surface reach the window below.
+fn set_input_region(
+    compositor: &Attached<WlCompositor>,
+    surface: &WlSurface,
+    location: &Location,
+    size: (u32, u32),
+    click_through: bool,
+) {
+    if !click_through {
+        surface.set_input_region(None);
+        return;
+    }
+    let region = compositor.create_region();
+    for (x, y, width, height) in slivers(location, size) {
+        region.add(x, y, width, height);
+    }
+    surface.set_input_region(Some(&region));
+    region.destroy();
+}
+
+/// The rectangles of the surface at the border of the output, as `(x, y, width, height)`.
+fn slivers(location: &Location, (width, height): (u32, u32)) -> Vec<(i32, i32, i32, i32)> {
+    let width = width.try_into().unwrap_or(i32::MAX);
+    let height = height.try_into().unwrap_or(i32::MAX);
+    let top = (0, 0, width, 1);
+    let bottom = (0, height - 1, width, 1);
+    let left = (0, 0, 1, height);
+    let right = (width - 1, 0, 1, height);
+    match location {
+        Location::TopLeft => vec![top, left],
+        Location::TopRight => vec![top, right],
+        Location::BottomRight => vec![bottom, right],
+        Location::BottomLeft => vec![bottom, left],
+        Location::Top => vec![top],
+        Location::Bottom => vec![bottom],
+        Location::Left => vec![left],
+        Location::Right => vec![right],
+    }
+}
+
+/// Whether the MIME type matches the pattern, which is either a MIME type or a wildcard like
+/// `image/*`.
+fn mime_type_matches(pattern: &str, mime_type: &str) -> bool {
+    let base = mime_type.split(';').next().unwrap_or_default();
+    match pattern.strip_suffix("/*") {
+        Some("*") => true,
+        Some(prefix) => base
+            .split('/')
+            .next()
+            .is_some_and(|value| value.eq_ignore_ascii_case(prefix)),
+        None => pattern.eq_ignore_ascii_case(mime_type) || pattern.eq_ignore_ascii_case(base),
+    }
+}
+
+/// The type to receive a drag offering the MIME types as when dropped onto the corner, if it
+/// may be dropped onto it. The data is always received as text, preferring the types the
+/// corner asks for.
+fn drop_mime_type(config: &CornerConfig, offered: &[String]) -> Option<String> {
+    let accepted = |mime_type: &str| {
+        config
+            .drop_mime_types
+            .iter()
+            .any(|pattern| mime_type_matches(pattern, mime_type))
+    };
+    if !config.drop_mime_types.is_empty() && !offered.iter().any(|value| accepted(value)) {
+        return None;
+    }
+    DROP_MIME_TYPES
+        .into_iter()
+        .filter(|mime_type| offered.iter().any(|value| value == mime_type))
+        .min_by_key(|mime_type| !accepted(mime_type))
+        .map(str::to_owned)
+}
+
+/// Turn the dropped data into the arguments of the drop command: the entries of a URI list,
+/// with local files as paths, or else the text itself.
+fn drop_items(mime_type: &str, data: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(data);
+    if mime_type != "text/uri-list" {
+        return vec![text.into_owned()];
+    }
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|uri| {
+            uri.strip_prefix("file://")
+                .and_then(|rest| rest.find('/').map(|start| percent_decode(&rest[start..])))
+                .unwrap_or_else(|| uri.to_owned())
+        })
+        .collect()
+}
+
+/// Decode the `%XX` escapes of a URI.
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| value.get(index + 1..index + 3))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                result.push(byte);
+                index += 3;
+            }
+            None => {
+                result.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+/// How far a touch moved from the start toward the inside of the output, away from the border
+/// of the location. Corners take the furthest of both directions.
+fn inward_distance(location: &Location, (start_x, start_y): (f64, f64), (x, y): (f64, f64)) -> f64 {
+    let (dx, dy) = (x - start_x, y - start_y);
+    match location {
+        Location::TopLeft => dx.max(dy),
+        Location::TopRight => (-dx).max(dy),
+        Location::BottomRight => (-dx).max(-dy),
+        Location::BottomLeft => dx.max(-dy),
+        Location::Top => dy,
+        Location::Bottom => -dy,
+        Location::Left => dx,
+        Location::Right => -dx,
+    }
+}
+
+/// Size of the layer surface of the location, edges are stretched along the output.
+fn layer_size(location: &Location, size: u8) -> (u32, u32) {
+    let size = size.into();
+    match location {
+        Location::Top | Location::Bottom => (0, size),
+        Location::Left | Location::Right => (size, 0),
+        _ => (size, size),
+    }
+}
+
+/// An output we created surfaces on, together with the description they were matched against.
+struct TrackedOutput {
+    output: WlOutput,
+    description: String,
+    _listener: OutputListener,
 }
 
 pub struct Wayland {
     pub preview: bool,
-    corner_to_surfaces: Vec<(Corner, Vec<WlSurface>)>,
+    config_path: PathBuf,
+    corner_to_surfaces: Vec<(Arc<Corner>, Vec<CornerSurface>)>,
+    outputs: Vec<TrackedOutput>,
+    subscribers: Arc<Subscribers>,
+    chords: Arc<Chords>,
+    corner_requests: channel::Sender<CornerRequest>,
+    /// Receiving side of `corner_requests`, until it is added to the event loop.
+    corner_request_channel: Option<Channel<CornerRequest>>,
+    /// Drops whose data was read on another thread.
+    received_drops: channel::Sender<(WlSeat, DropEvent)>,
+    /// Receiving side of `received_drops`, until it is added to the event loop.
+    received_drop_channel: Option<Channel<(WlSeat, DropEvent)>>,
+    drop_target: Option<DropTarget>,
+    /// Serial of the last drag that entered a surface on each seat, which tells whether the
+    /// data device still holds the offer of a drop.
+    drag_serials: Vec<(WlSeat, u32)>,
+    /// The surface a drag and drop hovers over.
+    drag_focus: Option<WlSurface>,
+    /// Selections of the first seat, if the compositor lets us read them.
+    selections: Option<Selections>,
+    /// Windows of all clients, if the compositor lets us manage them.
+    toplevels: Option<Toplevels>,
+}
+
+fn axis_index(axis: wl_pointer::Axis) -> usize {
+    match axis {
+        wl_pointer::Axis::HorizontalScroll => 1,
+        _ => 0,
+    }
 }
 
 impl Wayland {
-    pub fn new(configs: Vec<CornerConfig>, preview: bool) -> Self {
+    pub fn new(config_path: PathBuf, config: Config, preview: bool) -> Self {
+        let subscribers = Arc::new(Subscribers::default());
+        let chords = Arc::new(Chords::new(config.chords));
+        let (corner_requests, corner_request_channel) = channel::channel();
+        let (received_drops, received_drop_channel) = channel::channel();
         Wayland {
             preview,
-            corner_to_surfaces: configs
+            config_path,
+            corner_to_surfaces: config
+                .corners
                 .into_iter()
-                .map(|corner| (Corner::new(corner), vec![]))
+                .map(|corner| {
+                    (
+                        Arc::new(Corner::new(
+                            corner,
+                            Arc::clone(&subscribers),
+                            Arc::clone(&chords),
+                            corner_requests.clone(),
+                        )),
+                        vec![],
+                    )
+                })
                 .collect(),
+            outputs: vec![],
+            subscribers,
+            chords,
+            corner_requests,
+            corner_request_channel: Some(corner_request_channel),
+            received_drops,
+            received_drop_channel: Some(received_drop_channel),
+            drop_target: None,
+            drag_serials: vec![],
+            drag_focus: None,
+            selections: None,
+            toplevels: None,
         }
     }
 
     pub fn run(&mut self) -> Result<()> {
+        let mut event_loop = EventLoop::<GlobalState>::try_new()?;
+        // Signals are blocked for the current thread, so this needs to happen before spawning
+        // any of the corner threads as they would otherwise inherit the default handler.
+        event_loop
+            .handle()
+            .insert_source(Signals::new(&[Signal::SIGHUP])?, |_, _, global_state| {
+                info!("Received SIGHUP, reloading config");
+                global_state.reload_requested = true;
+            })
+            .map_err(|error| error.error)?;
+        if let Some(corner_request_channel) = self.corner_request_channel.take() {
+            event_loop
+                .handle()
+                .insert_source(corner_request_channel, |event, _, global_state| {
+                    if let channel::Event::Msg(request) = event {
+                        global_state.corner_requests.push(request);
+                    }
+                })
+                .map_err(|error| error.error)?;
+        }
+        if let Some(received_drop_channel) = self.received_drop_channel.take() {
+            event_loop
+                .handle()
+                .insert_source(received_drop_channel, |event, _, global_state| {
+                    if let channel::Event::Msg(event) = event {
+                        global_state.drop_events.push(event);
+                    }
+                })
+                .map_err(|error| error.error)?;
+        }
+        match ConfigWatcher::new(&self.config_path) {
+            Ok(watcher) => {
+                event_loop
+                    .handle()
+                    .insert_source(
+                        Generic::new(watcher, Interest::READ, Mode::Level),
+                        |_, watcher, global_state| {
+                            if watcher.changed() {
+                                info!("Config file changed, reloading config");
+                                global_state.reload_requested = true;
+                            }
+                            Ok(PostAction::Continue)
+                        },
+                    )
+                    .map_err(|error| error.error)?;
+            }
+            Err(error) => warn!("Not watching the config file for changes: {:?}", error),
+        }
+        match ControlSocket::bind() {
+            Ok(control_socket) => {
+                let (requests, request_channel) = channel::channel();
+                event_loop
+                    .handle()
+                    .insert_source(request_channel, |event, _, global_state| {
+                        if let channel::Event::Msg(request) = event {
+                            global_state.requests.push(request);
+                        }
+                    })
+                    .map_err(|error| error.error)?;
+                event_loop
+                    .handle()
+                    .insert_source(
+                        Generic::new(control_socket, Interest::READ, Mode::Level),
+                        move |_, control_socket, _| {
+                            control_socket.accept(&requests);
+                            Ok(PostAction::Continue)
+                        },
+                    )
+                    .map_err(|error| error.error)?;
+            }
+            Err(error) => warn!("Not listening for control requests: {:?}", error),
+        }
+
         let display = Display::connect_to_env()?;
         let mut event_queue = display.create_event_queue();
         let wl_display = Proxy::clone(&display).attach(event_queue.token());
@@ -75,7 +598,7 @@ impl Wayland {
         let sctk_data_device_manager = DataDeviceHandler::init(&mut seat_handler);
         let sctk_primary_selection_manager = PrimarySelectionHandler::init(&mut seat_handler);
 
-        let environment = smithay_client_toolkit::environment::Environment::new(
+        let mut environment = smithay_client_toolkit::environment::Environment::new(
             &wl_display,
             &mut event_queue,
             Waycorner {
@@ -88,89 +611,784 @@ impl Wayland {
                 sctk_data_device_manager,
                 sctk_primary_selection_manager,
                 layer_shell: SimpleGlobal::new(),
+                data_control: SimpleGlobal::new(),
+                tablet_manager: SimpleGlobal::new(),
+                toplevel_manager: ToplevelHandler::default(),
             },
         )?;
 
         let layer_shell = environment.require_global::<zwlr_layer_shell_v1::ZwlrLayerShellV1>();
-        let env_handle = environment.clone();
+
+        let mut global_state = GlobalState {
+            close_requested: false,
+            reload_requested: false,
+            output_updates: vec![],
+            requests: vec![],
+            corner_requests: vec![],
+            drop_events: vec![],
+        };
+
+        let data_device_result = environment.set_data_device_callback(|seat, event, mut ddata| {
+            let event = match event {
+                DndEvent::Enter {
+                    serial,
+                    surface,
+                    offer,
+                    ..
+                } => DropEvent::Enter {
+                    serial,
+                    surface,
+                    mime_types: offer
+                        .map(|offer| offer.with_mime_types(<[String]>::to_vec))
+                        .unwrap_or_default(),
+                },
+                DndEvent::Leave => DropEvent::Leave,
+                DndEvent::Drop { offer: Some(_) } => DropEvent::Drop,
+                _ => return,
+            };
+            if let Some(global_state) = ddata.get::<GlobalState>() {
+                global_state.drop_events.push((seat, event));
+            }
+        });
+        if let Err(error) = data_device_result {
+            warn!("Not accepting drops: {:?}", error);
+        }
 
         for output in environment.get_all_outputs() {
             if let Some(info) = with_output_info(&output, Clone::clone) {
-                self.output_handler(&env_handle, &layer_shell, output, &info)?;
+                global_state.output_updates.push((output, info));
             }
         }
+        let _output_listener = environment.listen_for_outputs(|output, info, mut ddata| {
+            if let Some(global_state) = ddata.get::<GlobalState>() {
+                global_state.output_updates.push((output, info.clone()));
+            }
+        });
 
-        let (tx, rx): (Sender<wl_pointer::Event>, Receiver<wl_pointer::Event>) = mpsc::channel();
+        let (tx, rx): (Sender<SeatPointerEvent>, Receiver<SeatPointerEvent>) = mpsc::channel();
 
-        for seat in environment.get_all_seats() {
+        self.toplevels = environment.with_inner(|inner| inner.toplevel_manager.toplevels());
+        if self.toplevels.is_none() {
+            warn!("Built-in actions are not available as the compositor does not support them");
+        }
+
+        let seats = environment.get_all_seats();
+        match (
+            environment.get_global::<ZwlrDataControlManagerV1>(),
+            seats.first(),
+        ) {
+            (Some(manager), Some(seat)) => self.selections = Some(Selections::new(&manager, seat)),
+            _ => warn!("Not reading the selections as the compositor does not support it"),
+        }
+        let (touch_tx, touch_rx): (Sender<SeatTouchEvent>, Receiver<SeatTouchEvent>) =
+            mpsc::channel();
+        let (tool_tx, tool_rx): (Sender<ToolEvent>, Receiver<ToolEvent>) = mpsc::channel();
+        let tablet_manager = environment.get_global::<ZwpTabletManagerV2>();
+        if tablet_manager.is_none() {
+            debug!("Not listening for tablets as the compositor does not support them");
+        }
+        // The surfaces the tablet tools are in proximity of.
+        let mut tool_focus: Vec<(ZwpTabletToolV2, WlSurface)> = vec![];
+        let mut pointers = vec![PointerState::default(); seats.len()];
+        let mut touch_points: Vec<Vec<TouchPoint>> = seats.iter().map(|_| vec![]).collect();
+        for (index, seat) in seats.into_iter().enumerate() {
             let filter_tx = tx.clone();
-            if let Some(has_ptr) = seat::with_seat_data(&seat, |seat_data| {
-                seat_data.has_pointer && !seat_data.defunct
+            let touch_tx = touch_tx.clone();
+            if let Some((has_ptr, has_touch)) = seat::with_seat_data(&seat, |seat_data| {
+                (
+                    seat_data.has_pointer && !seat_data.defunct,
+                    seat_data.has_touch && !seat_data.defunct,
+                )
             }) {
-                if !has_ptr {
-                    continue;
+                if has_ptr {
+                    let pointer = seat.get_pointer();
+                    pointers[index].version = pointer.as_ref().version();
+                    pointer.quick_assign(move |_, event, _| {
+                        filter_tx
+                            .send((index, event))
+                            .expect("could not send event on channel");
+                    });
+                }
+
+                if let Some(manager) = &tablet_manager {
+                    tablet::listen(manager, &seat, tool_tx.clone());
+                }
+
+                if has_touch {
+                    seat.get_touch().quick_assign(move |_, event, _| {
+                        touch_tx
+                            .send((index, event))
+                            .expect("could not send event on channel");
+                    });
+                }
+            }
+        }
+
+        self.corner_to_surfaces
+            .iter()
+            .for_each(|(corner, _)| Wayland::spawn_corner(corner));
+
+        WaylandSource::new(event_queue)
+            .quick_insert(event_loop.handle())
+            .map_err(|error| error.error)?;
+
+        loop {
+            if global_state.reload_requested {
+                global_state.reload_requested = false;
+                self.reload(&environment, &layer_shell)?;
+            }
+
+            for (output, info) in std::mem::take(&mut global_state.output_updates) {
+                self.output_handler(&environment, &layer_shell, output, &info)?;
+            }
+
+            for (stream, request) in std::mem::take(&mut global_state.requests) {
+                self.handle_request(stream, request);
+            }
+
+            for request in std::mem::take(&mut global_state.corner_requests) {
+                self.corner_request_handler(request);
+            }
+
+            for (seat, event) in std::mem::take(&mut global_state.drop_events) {
+                self.drop_handler(&environment, seat, event);
+            }
+
+            for (index, event) in rx.try_iter() {
+                self.pointer_handler(&mut pointers[index], event);
+            }
+
+            for (index, event) in touch_rx.try_iter() {
+                self.touch_handler(&mut touch_points[index], event);
+            }
+
+            for (tool, event) in tool_rx.try_iter() {
+                self.tool_handler(&mut tool_focus, tool, event);
+            }
+
+            if global_state.close_requested {
+                break;
+            }
+
+            display.flush().context("Wayland connection lost!")?;
+            event_loop
+                .dispatch(None, &mut global_state)
+                .context("Wayland connection lost!")?;
+        }
+        Ok(())
+    }
+
+    /// Let tablet tools in proximity of the surface of a corner with `tablet` enter it like the
+    /// pointer does.
+    fn tool_handler(
+        &self,
+        tool_focus: &mut Vec<(ZwpTabletToolV2, WlSurface)>,
+        tool: ZwpTabletToolV2,
+        event: zwp_tablet_tool_v2::Event,
+    ) {
+        let focus = tool_focus.iter().position(|(value, _)| *value == tool);
+        match event {
+            zwp_tablet_tool_v2::Event::ProximityIn { surface, .. } => {
+                let Some((corner, origin)) = self
+                    .get_corner(&surface)
+                    .filter(|(corner, _)| corner.config.tablet)
+                else {
+                    return;
+                };
+                corner.on_drag(false).ok();
+                corner.on_enter_mouse(origin).ok();
+                self.resize_handler(&surface, true);
+                tool_focus.push((tool, surface));
+            }
+            zwp_tablet_tool_v2::Event::Motion { x, y } => {
+                if let Some((_, surface)) = focus.map(|index| &tool_focus[index]) {
+                    self.motion_handler(surface, x, y, None);
+                }
+            }
+            zwp_tablet_tool_v2::Event::ProximityOut | zwp_tablet_tool_v2::Event::Removed => {
+                if let Some((_, surface)) = focus.map(|index| tool_focus.remove(index)) {
+                    self.get_corner(&surface)
+                        .and_then(|(corner, origin)| corner.on_leave_mouse(origin).ok());
+                    self.resize_handler(&surface, false);
                 }
+            }
+            _ => (),
+        }
+    }
 
-                seat.get_pointer().quick_assign(move |_, event, _| {
-                    filter_tx
-                        .send(event)
-                        .expect("could not send event on channel");
+    /// Recognise touches that start on a corner surface and move inward far enough as swipes.
+    fn touch_handler(&self, touch_points: &mut Vec<TouchPoint>, event: wl_touch::Event) {
+        match event {
+            wl_touch::Event::Down {
+                surface, id, x, y, ..
+            } if self
+                .get_corner_surface(&surface)
+                .is_some_and(|(corner, _)| !corner.config.swipe_command.is_empty()) =>
+            {
+                touch_points.push(TouchPoint {
+                    id,
+                    surface,
+                    start: (x, y),
+                    swiped: false,
                 });
             }
+            wl_touch::Event::Motion { id, x, y, .. } => {
+                let Some(point) = touch_points
+                    .iter_mut()
+                    .find(|point| point.id == id && !point.swiped)
+                else {
+                    return;
+                };
+                if let Some((corner, corner_surface)) = self.get_corner_surface(&point.surface) {
+                    let distance = inward_distance(&corner_surface.location, point.start, (x, y));
+                    if distance >= f64::from(corner.config.swipe_distance) {
+                        point.swiped = true;
+                        corner.on_swipe(self.origin(corner_surface)).ok();
+                    }
+                }
+            }
+            wl_touch::Event::Up { id, .. } => touch_points.retain(|point| point.id != id),
+            wl_touch::Event::Cancel => touch_points.clear(),
+            _ => (),
         }
+    }
 
-        let pointer_event_receiver = Arc::new(Mutex::new(rx));
-        thread::scope(|scope| -> Result<()> {
-            scope.spawn(|_| loop {
-                let event = pointer_event_receiver
-                    .lock()
-                    .expect("Could not lock event receiver")
-                    .recv();
-                match event {
-                    Ok(wl_pointer::Event::Enter { surface, .. }) => {
-                        self.get_corner(&surface)
-                            .and_then(|corner| corner.on_enter_mouse().ok());
+    fn pointer_handler(&self, pointer: &mut PointerState, event: wl_pointer::Event) {
+        match event {
+            wl_pointer::Event::Enter {
+                surface,
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                if let Some((corner, origin)) = self.get_corner(&surface) {
+                    corner.on_enter_mouse(origin).ok();
+                }
+                self.motion_handler(&surface, surface_x, surface_y, None);
+                self.resize_handler(&surface, true);
+                if let Some((_, corner_surface)) = self.get_corner_surface(&surface) {
+                    corner_surface
+                        .entered_sliver
+                        .set(corner_surface.click_through.get());
+                    corner_surface.pointer.set(Some((surface_x, surface_y)));
+                }
+                pointer.focus = Some(surface);
+            }
+            wl_pointer::Event::Motion {
+                time,
+                surface_x,
+                surface_y,
+            } => {
+                if let Some(surface) = &pointer.focus {
+                    self.motion_handler(surface, surface_x, surface_y, Some(time));
+                    if let Some((_, corner_surface)) = self.get_corner_surface(surface) {
+                        corner_surface.pointer.set(Some((surface_x, surface_y)));
                     }
-                    Ok(wl_pointer::Event::Leave { surface, .. }) => {
-                        self.get_corner(&surface)
-                            .and_then(|corner| corner.on_leave_mouse().ok());
+                }
+            }
+            wl_pointer::Event::Leave { surface, .. } => {
+                if let Some((corner, origin)) = self.get_corner(&surface) {
+                    corner.on_leave_mouse(origin).ok();
+                    if !pointer.pressed.is_empty() {
+                        corner.on_drag(false).ok();
                     }
-                    _ => (),
                 }
-            });
+                self.resize_handler(&surface, false);
+                if let Some((corner, corner_surface)) = self.get_corner_surface(&surface) {
+                    corner_surface.pointer.set(None);
+                    // The pointer left the sliver, so the whole corner takes input again.
+                    if corner.config.click_through == ClickThrough::AfterTrigger
+                        && corner_surface.entered_sliver.get()
+                    {
+                        corner_surface.set_click_through(false);
+                    }
+                }
+                pointer.focus = None;
+                pointer.scroll = [0.0; 2];
+                // Releasing buttons is only reported to the surface the pointer is on.
+                pointer.pressed.clear();
+            }
+            wl_pointer::Event::Axis { axis, value, .. } => {
+                pointer.frame_scroll[axis_index(axis)] += value;
+                if pointer.version < 5 {
+                    self.scroll_handler(pointer);
+                }
+            }
+            wl_pointer::Event::AxisDiscrete { axis, discrete } => {
+                pointer.frame_discrete[axis_index(axis)] += discrete;
+            }
+            wl_pointer::Event::AxisStop { axis, .. } => {
+                pointer.scroll[axis_index(axis)] = 0.0;
+            }
+            wl_pointer::Event::Frame => self.scroll_handler(pointer),
+            wl_pointer::Event::Button { button, state, .. } => {
+                let was_dragging = !pointer.pressed.is_empty();
+                pointer.pressed.retain(|value| *value != button);
+                if state == wl_pointer::ButtonState::Pressed {
+                    pointer.pressed.push(button);
+                }
+                let dragging = !pointer.pressed.is_empty();
+                let corner = pointer
+                    .focus
+                    .as_ref()
+                    .and_then(|surface| self.get_corner(surface));
+                if let Some((corner, origin)) = corner {
+                    if was_dragging != dragging {
+                        corner.on_drag(dragging).ok();
+                    }
+                    let name = config::BUTTONS
+                        .iter()
+                        .find(|(_, code)| *code == button)
+                        .map(|(name, _)| *name);
+                    if let (wl_pointer::ButtonState::Pressed, Some(name)) = (state, name) {
+                        corner.on_button(origin, name).ok();
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
 
-            self.corner_to_surfaces.iter().for_each(|(corner, _)| {
-                scope.spawn(move |_| loop {
-                    corner.wait().unwrap();
-                });
-            });
+    fn scroll_handler(&self, pointer: &mut PointerState) {
+        let [vertical, horizontal] = pointer.scroll_steps();
+        let (corner, origin) = match pointer
+            .focus
+            .as_ref()
+            .and_then(|surface| self.get_corner(surface))
+        {
+            Some(value) => value,
+            None => return,
+        };
+        let axes = [(vertical, "up", "down"), (horizontal, "left", "right")];
+        for (steps, negative, positive) in axes {
+            let direction = if steps < 0 { negative } else { positive };
+            for _ in 0..steps.unsigned_abs() {
+                corner.on_scroll(origin.clone(), direction).ok();
+            }
+        }
+    }
 
-            let mut global_state = GlobalState {
-                close_requested: false,
-            };
+    fn spawn_corner(corner: &Arc<Corner>) {
+        let corner = Arc::clone(corner);
+        thread::spawn(move || corner.wait());
+    }
 
-            loop {
-                event_queue
-                    .dispatch(&mut global_state, |_, _, _| {
-                        panic!("An event was received not assigned to any callback!")
-                    })
-                    .context("Wayland connection lost!")?;
+    fn handle_request(&self, stream: UnixStream, request: Request) {
+        let response = match request {
+            Request::List => Ok(self.list_corners()),
+            Request::Enable(name) => self.set_enabled(name, Some(true)),
+            Request::Disable(name) => self.set_enabled(name, Some(false)),
+            Request::Toggle(name) => self.set_enabled(name, None),
+            Request::Subscribe { json } => {
+                self.subscribers.add(stream, json);
+                return;
+            }
+        };
+        ipc::respond(stream, response);
+    }
+
+    /// Enable or disable the corner with the given name, or all corners. Toggles when `enabled`
+    /// is `None`.
+    fn set_enabled(&self, name: Option<String>, enabled: Option<bool>) -> Result<String> {
+        let corners = self
+            .corner_to_surfaces
+            .iter()
+            .map(|(corner, _)| corner)
+            .filter(|corner| name.as_ref().is_none_or(|name| &corner.config.name == name))
+            .collect::<Vec<_>>();
+        if let (Some(name), true) = (&name, corners.is_empty()) {
+            bail!("there is no corner named `{}`", name)
+        }
+        for corner in corners {
+            corner.set_enabled(enabled.unwrap_or(!corner.is_enabled()));
+        }
+        Ok(self.list_corners())
+    }
 
-                if global_state.close_requested {
-                    break;
+    /// One line per corner: its name, whether it is armed, its locations and matched outputs.
+    fn list_corners(&self) -> String {
+        let mut lines = self
+            .corner_to_surfaces
+            .iter()
+            .map(|(corner, surfaces)| {
+                let mut outputs: Vec<&str> = vec![];
+                for tracked in &self.outputs {
+                    if surfaces.iter().any(|value| value.output == tracked.output) {
+                        outputs.push(&tracked.description);
+                    }
                 }
+                let state = match (corner.is_enabled(), outputs.is_empty()) {
+                    (false, _) => "disabled",
+                    (true, true) => "unmatched",
+                    (true, false) => "armed",
+                };
+                format!(
+                    "{}\t{}\t{}\t{}\n",
+                    corner.config.name,
+                    state,
+                    corner
+                        .config
+                        .locations
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    outputs.join(", "),
+                )
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+        lines.concat()
+    }
+
+    /// Reload the config file and recreate the corners whose config changed.
+    ///
+    /// If the config file cannot be parsed, the error is logged and the current corners are kept.
+    fn reload(
+        &mut self,
+        environment: &Environment<Waycorner>,
+        layer_shell: &Attached<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+    ) -> Result<()> {
+        let Config {
+            corners: mut configs,
+            chords,
+        } = match get_configs(&self.config_path) {
+            Ok(config) => config,
+            Err(error) => {
+                error!("Keeping the current config, could not reload: {:?}", error);
+                return Ok(());
             }
-            Ok(())
-        })
-        .unwrap()
+        };
+        self.chords.set_chords(chords);
+
+        // Changed corners keep whether they were enabled, which is looked up by name.
+        let mut disabled = vec![];
+        self.corner_to_surfaces.retain(|(corner, surfaces)| {
+            if let Some(index) = configs.iter().position(|config| config == &corner.config) {
+                configs.remove(index);
+                return true;
+            }
+            info!("Removing corner: {:?}", corner.config);
+            if !corner.is_enabled() {
+                disabled.push(corner.config.name.clone());
+            }
+            surfaces.iter().for_each(CornerSurface::destroy);
+            corner.stop().ok();
+            false
+        });
+
+        let preview = self.preview;
+        for config in configs {
+            info!("Adding corner: {:?}", config);
+            let corner = Arc::new(Corner::new(
+                config,
+                Arc::clone(&self.subscribers),
+                Arc::clone(&self.chords),
+                self.corner_requests.clone(),
+            ));
+            if disabled.contains(&corner.config.name) {
+                corner.set_enabled(false);
+            }
+            let mut surfaces = vec![];
+            for tracked in &self.outputs {
+                Wayland::add_corner_surfaces(
+                    environment,
+                    layer_shell,
+                    &corner,
+                    &mut surfaces,
+                    &tracked.output,
+                    &tracked.description,
+                    preview,
+                )?;
+            }
+            Wayland::spawn_corner(&corner);
+            self.corner_to_surfaces.push((corner, surfaces));
+        }
+        Ok(())
+    }
+
+    fn get_corner(&self, surface: &WlSurface) -> Option<(&Corner, Origin)> {
+        self.get_corner_surface(surface)
+            .map(|(corner, corner_surface)| (corner, self.origin(corner_surface)))
     }
 
-    fn get_corner(&self, surface: &WlSurface) -> Option<&Corner> {
+    fn get_corner_surface(&self, surface: &WlSurface) -> Option<(&Corner, &CornerSurface)> {
         self.corner_to_surfaces
             .iter()
-            .filter(|(_, surfaces)| surfaces.iter().any(|value| value == surface))
-            .map(|(corner, _)| corner)
-            .next()
+            .find_map(|(corner, surfaces)| {
+                surfaces
+                    .iter()
+                    .find(|value| &value.surface == surface)
+                    .map(|value| (corner.as_ref(), value))
+            })
+    }
+
+    fn origin(&self, corner_surface: &CornerSurface) -> Origin {
+        let output = self
+            .outputs
+            .iter()
+            .find(|tracked| tracked.output == corner_surface.output)
+            .map(|tracked| tracked.description.clone())
+            .unwrap_or_default();
+        Origin {
+            location: corner_surface.location.clone(),
+            output,
+        }
+    }
+
+    fn corner_request_handler(&mut self, request: CornerRequest) {
+        match request {
+            CornerRequest::ClickThrough { corner, origin } => {
+                self.corner_to_surfaces
+                    .iter()
+                    .filter(|(value, _)| value.config.name == corner)
+                    .flat_map(|(_, surfaces)| surfaces)
+                    .filter(|surface| self.origin(surface) == origin)
+                    .for_each(|surface| {
+                        debug!("Making the {} corner click-through", surface.location);
+                        surface.set_click_through(true);
+                    });
+            }
+            CornerRequest::RunAction {
+                corner,
+                action: Action::ShowDesktop,
+                origin,
+            } => {
+                let Some(toplevels) = self.toplevels.as_mut() else {
+                    return;
+                };
+                let restore = self
+                    .corner_to_surfaces
+                    .iter()
+                    .find(|(value, _)| value.config.name == corner)
+                    .map(|(value, _)| value.config.restore);
+                if restore == Some(Restore::Toggle) && toplevels.has_minimized(&corner) {
+                    toplevels.restore(&corner);
+                } else if let Some(tracked) = self
+                    .outputs
+                    .iter()
+                    .find(|tracked| tracked.description == origin.output)
+                {
+                    toplevels.show_desktop(&corner, &tracked.output);
+                }
+            }
+            CornerRequest::RestoreAction { corner } => {
+                if let Some(toplevels) = self.toplevels.as_mut() {
+                    toplevels.restore(&corner);
+                }
+            }
+            CornerRequest::ReadSelection { kind, reply } => {
+                let pipe = self
+                    .selections
+                    .as_ref()
+                    .map(|selections| selections.receive(kind))
+                    .transpose()
+                    .map(Option::flatten);
+                match pipe {
+                    Ok(Some(mut pipe)) => {
+                        // The text only arrives once the request is flushed, so it is read on
+                        // another thread.
+                        thread::spawn(move || {
+                            let mut text = vec![];
+                            if let Err(error) = pipe.read_to_end(&mut text) {
+                                warn!("Could not read the {}: {:?}", kind.as_str(), error);
+                            }
+                            reply.send(String::from_utf8_lossy(&text).into_owned()).ok();
+                        });
+                    }
+                    Ok(None) => {
+                        reply.send(String::new()).ok();
+                    }
+                    Err(error) => {
+                        warn!("Could not receive the {}: {:?}", kind.as_str(), error);
+                        reply.send(String::new()).ok();
+                    }
+                }
+            }
+        }
+    }
+
+    fn drop_handler(
+        &mut self,
+        environment: &Environment<Waycorner>,
+        seat: WlSeat,
+        event: DropEvent,
+    ) {
+        let with_offer = |seat: &WlSeat, f: &dyn Fn(&DataOffer)| {
+            environment
+                .with_data_device(seat, |device| {
+                    device.with_dnd(|offer| offer.into_iter().for_each(f))
+                })
+                .ok();
+        };
+        match event {
+            DropEvent::Enter {
+                serial,
+                surface,
+                mime_types,
+            } => {
+                self.drop_leave();
+                self.drag_leave();
+                if let Some((corner, origin)) = self.get_corner(&surface) {
+                    if corner.config.accepts_drags() {
+                        corner.on_drag(true).ok();
+                        corner.on_enter_mouse(origin).ok();
+                        self.drag_focus = Some(surface.clone());
+                    }
+                }
+                self.drag_serials.retain(|(value, _)| *value != seat);
+                self.drag_serials.push((seat.clone(), serial));
+                let mime_type = self.get_corner(&surface).and_then(|(corner, _)| {
+                    (corner.is_enabled() && !corner.config.drop_command.is_empty())
+                        .then(|| drop_mime_type(&corner.config, &mime_types))
+                        .flatten()
+                });
+                debug!(
+                    "Drag offering {:?} entered, accepting {:?}",
+                    mime_types, mime_type
+                );
+                with_offer(&seat, &|offer| {
+                    offer.accept(mime_type.clone());
+                    if mime_type.is_some() {
+                        // The data is read before the drop is finished, so it may be moved.
+                        offer.set_actions(DndAction::Copy | DndAction::Move, DndAction::Copy);
+                    } else {
+                        offer.set_actions(DndAction::None, DndAction::None);
+                    }
+                });
+                if let Some(mime_type) = mime_type {
+                    self.drop_highlight(&surface, true);
+                    self.drop_target = Some(DropTarget {
+                        seat,
+                        serial,
+                        surface,
+                        mime_type,
+                    });
+                }
+            }
+            DropEvent::Leave => {
+                self.drop_leave();
+                self.drag_leave();
+            }
+            DropEvent::Drop => {
+                // No leave follows a drop.
+                self.drag_leave();
+                let Some(target) = self.drop_target.take_if(|target| target.seat == seat) else {
+                    return;
+                };
+                self.drop_highlight(&target.surface, false);
+                let mut pipe = None;
+                environment
+                    .with_data_device(&seat, |device| {
+                        pipe = device.with_dnd(|offer| {
+                            offer.map(|offer| offer.receive(target.mime_type.clone()))
+                        });
+                    })
+                    .ok();
+                match pipe {
+                    Some(Ok(mut pipe)) => {
+                        let received_drops = self.received_drops.clone();
+                        // The data only arrives once the events are flushed, so it is read on
+                        // another thread.
+                        thread::spawn(move || {
+                            let mut data = vec![];
+                            match pipe.read_to_end(&mut data) {
+                                Ok(_) => {
+                                    received_drops
+                                        .send((seat, DropEvent::Received { target, data }))
+                                        .ok();
+                                }
+                                Err(error) => warn!("Could not read the drop: {:?}", error),
+                            }
+                        });
+                    }
+                    Some(Err(error)) => warn!("Could not receive the drop: {:?}", error),
+                    None => (),
+                }
+            }
+            DropEvent::Received { target, data } => {
+                if let Some((corner, origin)) = self.get_corner(&target.surface) {
+                    corner
+                        .on_drop(origin, drop_items(&target.mime_type, &data))
+                        .ok();
+                }
+                // A later drag replaces the offer of the drop.
+                if !self.drag_serials.contains(&(seat.clone(), target.serial)) {
+                    return;
+                }
+                with_offer(&seat, &|offer| {
+                    // Finishing is only allowed once an action was negotiated.
+                    if offer.get_current_action() != DndAction::None {
+                        offer.finish();
+                    }
+                });
+            }
+        }
+    }
+
+    /// The drag left the corner, or ended on it.
+    fn drag_leave(&mut self) {
+        if let Some(surface) = self.drag_focus.take() {
+            if let Some((corner, origin)) = self.get_corner(&surface) {
+                corner.on_leave_mouse(origin).ok();
+                corner.on_drag(false).ok();
+            }
+        }
+    }
+
+    fn drop_leave(&mut self) {
+        if let Some(target) = self.drop_target.take() {
+            self.drop_highlight(&target.surface, false);
+        }
+    }
+
+    /// Show the corner while a drag that can be dropped onto it hovers over it, if it should.
+    fn drop_highlight(&self, surface: &WlSurface, highlight: bool) {
+        if let Some((corner, corner_surface)) = self.get_corner_surface(surface) {
+            if corner.config.drop_highlight {
+                corner_surface.fill(if highlight || self.preview {
+                    corner.config.color
+                } else {
+                    config::COLOR_TRANSPARENT
+                });
+            }
+        }
+    }
+
+    /// Grow the surface to the `leave_size` of its corner while the pointer is on it, so the
+    /// pointer has to move further away to leave the corner.
+    fn resize_handler(&self, surface: &WlSurface, entered: bool) {
+        if let Some((corner, corner_surface)) = self.get_corner_surface(surface) {
+            if let Some(leave_size) = corner.config.leave_size {
+                corner_surface.resize(if entered {
+                    leave_size
+                } else {
+                    corner.config.size
+                });
+            }
+        }
+    }
+
+    /// Forward the pointer position on a corner surface to its corner.
+    fn motion_handler(&self, surface: &WlSurface, x: f64, y: f64, time: Option<u32>) {
+        if let Some((corner, corner_surface)) = self
+            .get_corner_surface(surface)
+            .filter(|(corner, _)| corner.config.tracks_motion())
+        {
+            let (width, height) = corner_surface.size.get();
+            corner
+                .on_motion(
+                    self.origin(corner_surface),
+                    Position {
+                        x,
+                        y,
+                        width,
+                        height,
+                    },
+                    time,
+                )
+                .ok();
+        }
     }
 
     fn output_handler(
@@ -181,44 +1399,107 @@ impl Wayland {
         info: &OutputInfo,
     ) -> Result<()> {
         info!("{:?}", info);
-        let preview = self.preview;
+        let tracked = self
+            .outputs
+            .iter()
+            .position(|tracked| tracked.output == output);
 
-        self.corner_to_surfaces
-            .iter_mut()
-            .try_for_each(|(corner, surfaces)| -> Result<()> {
-                debug!("{:?}", corner);
-                if !corner.is_match(info.description.as_str()) {
-                    debug!("Output description is NOT a match");
-                    return Ok(());
-                }
-                debug!("Output description IS a match");
+        if info.obsolete {
+            if let Some(index) = tracked {
+                self.remove_surfaces(&output);
+                self.outputs.remove(index);
+                info!("Releasing output");
+                output.release();
+            }
+            return Ok(());
+        }
 
-                if info.obsolete {
-                    debug!("Clearing surfaces");
-                    surfaces.clear();
-                    return Ok(());
-                }
+        match tracked {
+            Some(index) if self.outputs[index].description == info.description => {
+                debug!("Output description did not change");
+                return Ok(());
+            }
+            Some(index) => {
+                debug!("Output description changed");
+                self.remove_surfaces(&output);
+                self.outputs[index].description = info.description.clone();
+            }
+            None => {
+                let listener = add_output_listener(&output, |output, info, mut ddata| {
+                    if let Some(global_state) = ddata.get::<GlobalState>() {
+                        global_state.output_updates.push((output, info.clone()));
+                    }
+                });
+                self.outputs.push(TrackedOutput {
+                    output: output.clone(),
+                    description: info.description.clone(),
+                    _listener: listener,
+                });
+            }
+        }
 
-                debug!("Adding surfaces");
-                let mut corner_surfaces = Wayland::corner_setup(
+        let preview = self.preview;
+        self.corner_to_surfaces
+            .iter_mut()
+            .try_for_each(|(corner, surfaces)| {
+                Wayland::add_corner_surfaces(
                     environment,
                     layer_shell,
+                    corner,
+                    surfaces,
                     &output,
-                    corner.config.clone(),
+                    &info.description,
                     preview,
-                    corner.config.color,
-                )?;
+                )
+            })
+    }
 
-                surfaces.append(&mut corner_surfaces);
-                Ok(())
-            })?;
-        if info.obsolete {
-            info!("Releasing output");
-            output.release();
+    /// Create the surfaces of a corner on the given output if its description matches.
+    fn add_corner_surfaces(
+        environment: &Environment<Waycorner>,
+        layer_shell: &Attached<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+        corner: &Corner,
+        surfaces: &mut Vec<CornerSurface>,
+        output: &WlOutput,
+        description: &str,
+        preview: bool,
+    ) -> Result<()> {
+        debug!("{:?}", corner);
+        if !corner.is_match(description) {
+            debug!("Output description is NOT a match");
+            return Ok(());
         }
+        debug!("Output description IS a match");
+
+        debug!("Adding surfaces");
+        let mut corner_surfaces = Wayland::corner_setup(
+            environment,
+            layer_shell,
+            output,
+            corner.config.clone(),
+            preview,
+            corner.config.color,
+        )?;
+
+        surfaces.append(&mut corner_surfaces);
         Ok(())
     }
 
+    /// Destroy the surfaces of all corners that live on the given output.
+    fn remove_surfaces(&mut self, output: &WlOutput) {
+        debug!("Clearing surfaces");
+        for (_, surfaces) in self.corner_to_surfaces.iter_mut() {
+            surfaces.retain(|corner_surface| {
+                if &corner_surface.output == output {
+                    corner_surface.destroy();
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+
     fn corner_setup(
         environment: &Environment<Waycorner>,
         layer_shell: &Attached<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
@@ -226,7 +1507,7 @@ impl Wayland {
         corner_config: CornerConfig,
         preview: bool,
         preview_color: u32,
-    ) -> Result<Vec<WlSurface>> {
+    ) -> Result<Vec<CornerSurface>> {
         corner_config
             .locations
             .iter()
@@ -251,18 +1532,9 @@ impl Wayland {
                     zwlr_layer_shell_v1::Layer::Overlay,
                     "waycorner".to_owned(),
                 );
-                let size = corner_config.size.into();
+                let (width, height) = layer_size(location, corner_config.size);
                 let margin = corner_config.margin.into();
-                layer_surface.set_size(
-                    match location {
-                        Location::Top | Location::Bottom => 0,
-                        _ => size,
-                    },
-                    match location {
-                        Location::Left | Location::Right => 0,
-                        _ => size,
-                    },
-                );
+                layer_surface.set_size(width, height);
                 layer_surface.set_margin(
                     // top, right, bottom, left
                     match location {
@@ -286,79 +1558,155 @@ impl Wayland {
                 // Ignore exclusive zones.
                 layer_surface.set_exclusive_zone(-1);
 
-                Wayland::initial_draw(
-                    environment,
-                    surface.clone(),
+                let corner_surface = CornerSurface {
+                    output: output.clone(),
+                    location: location.clone(),
+                    surface,
                     layer_surface,
-                    preview,
-                    preview_color,
-                )?;
-
-                Ok(surface)
+                    size: Rc::new(Cell::new((0, 0))),
+                    compositor: environment.require_global::<WlCompositor>(),
+                    click_through: Rc::new(Cell::new(
+                        corner_config.click_through == ClickThrough::Always,
+                    )),
+                    entered_sliver: Cell::new(false),
+                    pointer: Cell::new(None),
+                    pool: Rc::new(RefCell::new(
+                        environment
+                            .create_double_pool(|_| {})
+                            .context("Failed to create double pool!")?,
+                    )),
+                    color: Rc::new(Cell::new(if preview {
+                        preview_color
+                    } else {
+                        config::COLOR_TRANSPARENT
+                    })),
+                };
+                Wayland::initial_draw(&corner_surface);
+                Ok(corner_surface)
             })
             .collect()
     }
 
-    fn initial_draw(
-        environment: &Environment<Waycorner>,
-        surface: WlSurface,
-        layer_surface: Main<ZwlrLayerSurfaceV1>,
-        preview: bool,
-        preview_color: u32,
-    ) -> Result<()> {
-        let mut double_pool = environment
-            .create_double_pool(|_| {})
-            .context("Failed to create double pool!")?;
+    fn initial_draw(corner_surface: &CornerSurface) {
+        let surface_handle = corner_surface.surface.clone();
+        let compositor = corner_surface.compositor.clone();
+        let location = corner_surface.location.clone();
+        let size = Rc::clone(&corner_surface.size);
+        let click_through = Rc::clone(&corner_surface.click_through);
+        let pool = Rc::clone(&corner_surface.pool);
+        let color = Rc::clone(&corner_surface.color);
 
-        let surface_handle = surface.clone();
+        corner_surface
+            .layer_surface
+            .quick_assign(move |layer_surface, event, _| {
+                if let zwlr_layer_surface_v1::Event::Configure {
+                    serial,
+                    width,
+                    height,
+                } = event
+                {
+                    layer_surface.ack_configure(serial);
+                    size.set((width, height));
+                    if draw(&pool, &surface_handle, (width, height), color.get()) {
+                        set_input_region(
+                            &compositor,
+                            &surface_handle,
+                            &location,
+                            (width, height),
+                            click_through.get(),
+                        );
+                        surface_handle.commit();
+                    }
+                }
+            });
 
-        layer_surface.quick_assign(move |layer_surface, event, _| {
-            if let zwlr_layer_surface_v1::Event::Configure {
-                serial,
-                width,
-                height,
-            } = event
-            {
-                layer_surface.ack_configure(serial);
-                if let Some(pool) = double_pool.pool() {
-                    let pxcount = width * height;
-                    let bytecount = 4 * pxcount;
+        corner_surface.surface.commit();
+    }
+}
 
-                    pool.resize(bytecount.try_into().unwrap()).unwrap();
-                    pool.seek(SeekFrom::Start(0)).unwrap();
-                    {
-                        let mut writer = BufWriter::new(&mut *pool);
-                        let color = if preview {
-                            preview_color
-                        } else {
-                            config::COLOR_TRANSPARENT
-                        };
-                        for _ in 0..pxcount {
-                            writer.write_all(&color.to_ne_bytes()).unwrap();
-                        }
-                        writer.flush().unwrap();
-                    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                    let buffer = pool.buffer(
-                        0,
-                        width.try_into().unwrap(),
-                        height.try_into().unwrap(),
-                        (4 * width).try_into().unwrap(),
-                        Format::Argb8888,
-                    );
-                    surface_handle.attach(Some(&buffer), 0, 0);
-                    surface_handle.damage_buffer(
-                        0,
-                        0,
-                        width.try_into().unwrap(),
-                        height.try_into().unwrap(),
-                    );
-                    surface_handle.commit();
-                }
-            }
-        });
+    fn scroll(state: &mut PointerState, vertical: f64, horizontal: f64) -> [i32; 2] {
+        state.frame_scroll = [vertical, horizontal];
+        state.scroll_steps()
+    }
 
-        surface.commit();
-        Ok(())
+    #[test]
+    fn scroll_steps_carry_remainder() {
+        let mut state = PointerState::default();
+        assert_eq!(scroll(&mut state, 10.0, 0.0), [0, 0]);
+        assert_eq!(scroll(&mut state, 10.0, 0.0), [1, 0]);
+        assert_eq!(state.scroll, [5.0, 0.0]);
+        assert_eq!(scroll(&mut state, 40.0, 31.0), [3, 2]);
+        assert_eq!(state.scroll, [0.0, 1.0]);
+        assert_eq!(state.frame_scroll, [0.0; 2]);
+    }
+
+    #[test]
+    fn scroll_steps_negative() {
+        let mut state = PointerState::default();
+        assert_eq!(scroll(&mut state, -20.0, -14.0), [-1, 0]);
+        assert_eq!(state.scroll, [-5.0, -14.0]);
+        // Scrolling back first cancels out the remainder.
+        assert_eq!(scroll(&mut state, 10.0, -1.0), [0, -1]);
+        assert_eq!(state.scroll, [5.0, 0.0]);
+    }
+
+    #[test]
+    fn scroll_steps_discrete_reset_remainder() {
+        let mut state = PointerState::default();
+        assert_eq!(scroll(&mut state, 10.0, -10.0), [0, 0]);
+        state.frame_scroll = [15.0, 0.0];
+        state.frame_discrete = [1, 0];
+        assert_eq!(state.scroll_steps(), [1, 0]);
+        assert_eq!(state.scroll, [0.0, -10.0]);
+        assert_eq!(state.frame_discrete, [0; 2]);
+        assert_eq!(scroll(&mut state, 10.0, 0.0), [0, 0]);
+    }
+
+    #[test]
+    fn mime_type_patterns() {
+        assert!(mime_type_matches("text/plain", "text/plain"));
+        assert!(mime_type_matches("text/plain", "text/plain;charset=utf-8"));
+        assert!(mime_type_matches("TEXT/PLAIN", "text/plain"));
+        assert!(mime_type_matches("image/*", "image/png"));
+        assert!(mime_type_matches("*/*", "application/pdf"));
+        assert!(!mime_type_matches("image/*", "text/plain"));
+        assert!(!mime_type_matches("text/plain", "text/html"));
+        assert!(!mime_type_matches("text/plain;charset=utf-8", "text/plain"));
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert_eq!(percent_decode("/a%20b"), "/a b");
+        assert_eq!(percent_decode("/%C3%A9t%c3%a9"), "/été");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%2"), "%2");
+        assert_eq!(percent_decode("%zz%+1"), "%zz%+1");
+        assert_eq!(percent_decode("%%41"), "%A");
+    }
+
+    #[test]
+    fn dropped_uri_list() {
+        let data = b"# comment\r\nfile:///home/user/My%20File.txt\r\n\r\nfile://host/tmp/a\r\nhttps://example.com/a%20b\r\n";
+        assert_eq!(
+            drop_items("text/uri-list", data),
+            [
+                "/home/user/My File.txt",
+                "/tmp/a",
+                "https://example.com/a%20b"
+            ]
+        );
+    }
+
+    #[test]
+    fn dropped_text() {
+        assert_eq!(
+            drop_items("text/plain;charset=utf-8", b"file:///a\nsecond line"),
+            ["file:///a\nsecond line"]
+        );
+        assert_eq!(drop_items("text/uri-list", b""), Vec::<String>::new());
     }
 }